@@ -3,7 +3,10 @@
 use std::fmt;
 
 /// A diagnostic message about a bit of code.
+///
+/// Make these with [`Diagnostic::new`], since more fields may be added.
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct Diagnostic {
   /// The range of the file this diagnostic applies to.
   pub range: text_pos::RangeUtf16,
@@ -11,6 +14,16 @@ pub struct Diagnostic {
   pub message: String,
   /// The severity.
   pub severity: Severity,
+  /// The code.
+  pub code: Code,
+}
+
+impl Diagnostic {
+  /// Returns a new diagnostic.
+  #[must_use]
+  pub fn new(range: text_pos::RangeUtf16, message: String, severity: Severity, code: Code) -> Self {
+    Self { range, message, severity, code }
+  }
 }
 
 impl fmt::Display for Diagnostic {
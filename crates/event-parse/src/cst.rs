@@ -0,0 +1,290 @@
+//! A compact concrete syntax tree stored in arenas, and a [`Sink`] for it.
+//!
+//! Unlike with Rowan, there are no separate red and green trees. The tree owns its text, all of its
+//! tokens are in one vector, and each node covers a range of that vector. Nodes and tokens are
+//! referred to by [`NodeId`] and [`TokenId`], which are indices into the tree.
+
+use crate::Sink;
+use always::convert::{u32_to_usize, usize_to_u32};
+use std::ops::Range;
+use token::{Token, Triviable};
+
+/// A node in a [`Tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u32);
+
+/// A token in a [`Tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TokenId(u32);
+
+/// A child of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Element {
+  /// A node.
+  Node(NodeId),
+  /// A token.
+  Token(TokenId),
+}
+
+#[derive(Debug)]
+struct NodeData<K> {
+  kind: K,
+  /// The tokens in this node, including those in descendants.
+  start: u32,
+  end: u32,
+  parent: Option<NodeId>,
+  first_child: Option<NodeId>,
+  next_sibling: Option<NodeId>,
+}
+
+#[derive(Debug)]
+struct TokenData<K> {
+  kind: K,
+  /// The range in the text.
+  start: u32,
+  end: u32,
+  parent: NodeId,
+}
+
+/// A concrete syntax tree.
+#[derive(Debug)]
+pub struct Tree<K> {
+  text: String,
+  nodes: Vec<NodeData<K>>,
+  tokens: Vec<TokenData<K>>,
+}
+
+impl<K> Tree<K>
+where
+  K: Copy,
+{
+  /// Returns the root node.
+  #[must_use]
+  pub fn root(&self) -> NodeId {
+    NodeId(0)
+  }
+
+  /// Returns the whole text of the tree.
+  #[must_use]
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  /// Returns the kind of the node.
+  #[must_use]
+  pub fn kind(&self, node: NodeId) -> K {
+    self.node(node).kind
+  }
+
+  /// Returns the parent of the node, or `None` if it is the root.
+  #[must_use]
+  pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+    self.node(node).parent
+  }
+
+  /// Returns the first child node of the node.
+  #[must_use]
+  pub fn first_child(&self, node: NodeId) -> Option<NodeId> {
+    self.node(node).first_child
+  }
+
+  /// Returns the next sibling node of the node.
+  #[must_use]
+  pub fn next_sibling(&self, node: NodeId) -> Option<NodeId> {
+    self.node(node).next_sibling
+  }
+
+  /// Returns the child nodes of the node.
+  pub fn child_nodes(&self, node: NodeId) -> impl Iterator<Item = NodeId> {
+    std::iter::successors(self.first_child(node), |&node| self.next_sibling(node))
+  }
+
+  /// Returns the child nodes and tokens of the node, in order.
+  pub fn children(&self, node: NodeId) -> impl Iterator<Item = Element> {
+    let data = self.node(node);
+    let mut tok = data.start;
+    let end = data.end;
+    let mut child = data.first_child;
+    std::iter::from_fn(move || {
+      if let Some(node) = child {
+        let data = self.node(node);
+        if data.start <= tok {
+          tok = data.end;
+          child = data.next_sibling;
+          return Some(Element::Node(node));
+        }
+      }
+      (tok < end).then(|| {
+        tok += 1;
+        Element::Token(TokenId(tok - 1))
+      })
+    })
+  }
+
+  /// Returns all the tokens in the node, including those in descendants.
+  pub fn tokens(&self, node: NodeId) -> impl Iterator<Item = TokenId> + use<K> {
+    let data = self.node(node);
+    (data.start..data.end).map(TokenId)
+  }
+
+  /// Returns the range of the node in the text.
+  #[must_use]
+  pub fn range(&self, node: NodeId) -> Range<usize> {
+    let data = self.node(node);
+    if data.start < data.end {
+      let start = self.token(TokenId(data.start)).start;
+      let end = self.token(TokenId(data.end - 1)).end;
+      return u32_to_usize(start)..u32_to_usize(end);
+    }
+    // an empty node, right after the token before it, if any.
+    let offset = data.start.checked_sub(1).map_or(0, |idx| self.token(TokenId(idx)).end);
+    u32_to_usize(offset)..u32_to_usize(offset)
+  }
+
+  /// Returns the text of the node.
+  #[must_use]
+  pub fn node_text(&self, node: NodeId) -> &str {
+    &self.text[self.range(node)]
+  }
+
+  /// Returns the kind of the token.
+  #[must_use]
+  pub fn token_kind(&self, token: TokenId) -> K {
+    self.token(token).kind
+  }
+
+  /// Returns the range of the token in the text.
+  #[must_use]
+  pub fn token_range(&self, token: TokenId) -> Range<usize> {
+    let data = self.token(token);
+    u32_to_usize(data.start)..u32_to_usize(data.end)
+  }
+
+  /// Returns the text of the token.
+  #[must_use]
+  pub fn token_text(&self, token: TokenId) -> &str {
+    &self.text[self.token_range(token)]
+  }
+
+  /// Returns the node containing the token.
+  #[must_use]
+  pub fn token_parent(&self, token: TokenId) -> NodeId {
+    self.token(token).parent
+  }
+
+  fn node(&self, node: NodeId) -> &NodeData<K> {
+    &self.nodes[u32_to_usize(node.0)]
+  }
+
+  fn token(&self, token: TokenId) -> &TokenData<K> {
+    &self.tokens[u32_to_usize(token.0)]
+  }
+}
+
+/// The sink, which builds a [`Tree`].
+#[derive(Debug)]
+pub struct CstSink<K, E> {
+  tree: Tree<K>,
+  /// The nodes we're in, and their last child so far.
+  stack: Vec<(NodeId, Option<NodeId>)>,
+  errors: Vec<Error<K, E>>,
+  no_range: Vec<E>,
+}
+
+impl<K, E> CstSink<K, E>
+where
+  K: Copy,
+{
+  /// Finish the tree.
+  ///
+  /// The errors are sorted by the start of their range, so they are in source order.
+  ///
+  /// # Panics
+  ///
+  /// If there was no root node, or if not all nodes were exited.
+  #[must_use]
+  pub fn finish(mut self) -> (Tree<K>, Vec<Error<K, E>>) {
+    assert!(!self.tree.nodes.is_empty(), "no root node");
+    assert!(self.stack.is_empty(), "not all nodes were exited");
+    self.extend_errors();
+    // errors for nodes are only known on exit, after errors inside them. stable, so errors at the
+    // same place stay in the order they were reported.
+    self.errors.sort_by_key(|e| e.range.start);
+    (self.tree, self.errors)
+  }
+
+  fn extend_errors(&mut self) {
+    let last = self.tree.tokens.last();
+    let range = last.map_or(0..0, |tok| u32_to_usize(tok.start)..u32_to_usize(tok.end));
+    let kind = last.map(|tok| tok.kind);
+    let errors = std::mem::take(&mut self.no_range);
+    self.errors.extend(errors.into_iter().map(|inner| Error { range: range.clone(), kind, inner }));
+  }
+}
+
+impl<K, E> Default for CstSink<K, E> {
+  fn default() -> Self {
+    Self {
+      tree: Tree { text: String::new(), nodes: Vec::new(), tokens: Vec::new() },
+      stack: Vec::new(),
+      errors: Vec::new(),
+      no_range: Vec::new(),
+    }
+  }
+}
+
+impl<K, E> Sink<K, E> for CstSink<K, E>
+where
+  K: Copy + Triviable,
+{
+  fn enter(&mut self, kind: K) {
+    let node = NodeId(usize_to_u32(self.tree.nodes.len()));
+    let start = usize_to_u32(self.tree.tokens.len());
+    let parent = self.stack.last_mut().map(|(parent, last)| {
+      match last.replace(node) {
+        Some(prev) => self.tree.nodes[u32_to_usize(prev.0)].next_sibling = Some(node),
+        None => self.tree.nodes[u32_to_usize(parent.0)].first_child = Some(node),
+      }
+      *parent
+    });
+    assert!(parent.is_some() || self.tree.nodes.is_empty(), "more than one root node");
+    let data = NodeData { kind, start, end: start, parent, first_child: None, next_sibling: None };
+    self.tree.nodes.push(data);
+    self.stack.push((node, None));
+  }
+
+  fn token(&mut self, token: Token<'_, K>) {
+    let &(parent, _) = self.stack.last().expect("token with no nodes");
+    let start = usize_to_u32(self.tree.text.len());
+    self.tree.text.push_str(token.text);
+    let end = usize_to_u32(self.tree.text.len());
+    self.tree.tokens.push(TokenData { kind: token.kind, start, end, parent });
+    if !token.kind.is_trivia() {
+      self.extend_errors();
+    }
+  }
+
+  fn exit(&mut self) {
+    let (node, _) = self.stack.pop().expect("exit with no nodes");
+    self.tree.nodes[u32_to_usize(node.0)].end = usize_to_u32(self.tree.tokens.len());
+  }
+
+  fn error(&mut self, error: E) {
+    self.no_range.push(error);
+  }
+
+  fn error_range(&mut self, error: E, range: Range<usize>) {
+    self.errors.push(Error { range, kind: None, inner: error });
+  }
+}
+
+/// An error.
+#[derive(Debug, Clone)]
+pub struct Error<K, E> {
+  /// The range in the text.
+  pub range: Range<usize>,
+  /// The syntax kind of the token at the error, if the error was for one token.
+  pub kind: Option<K>,
+  /// The inner error.
+  pub inner: E,
+}
@@ -0,0 +1,130 @@
+//! A [`Sink`] that renders the tree as indented text, for snapshot tests.
+//!
+//! As with `RowanSink`, errors without a range are given the range of the next non-trivia token, or
+//! the last token if there was no such token, and are rendered just before that token. Errors with
+//! a range are rendered before the next token or exit. For instance, for `(a ) ` with a missing
+//! name before the `)`:
+//!
+//! ```text
+//! Root@0..5
+//!   List@0..4
+//!     LRound@0..1 "("
+//!     Name@1..2 "a"
+//!     Ws@2..3 " "
+//!     error@3..4: expected a name
+//!     RRound@3..4 ")"
+//!   Ws@4..5 " "
+//! ```
+
+use crate::Sink;
+use std::fmt::{self, Write as _};
+use std::ops::Range;
+use std::path::Path;
+use token::{Token, Triviable};
+
+/// The sink, which renders as it goes.
+#[derive(Debug, Default)]
+pub struct DebugSink {
+  lines: Vec<String>,
+  /// The lines for the nodes we're in, and where they started.
+  nodes: Vec<(usize, usize)>,
+  cur: (usize, usize),
+  no_range: Vec<String>,
+  ranged: Vec<(Range<usize>, String)>,
+}
+
+impl DebugSink {
+  /// Finish the rendering.
+  #[must_use]
+  pub fn finish(mut self) -> String {
+    self.extend_ranged();
+    self.extend_errors();
+    let mut ret = String::new();
+    for line in self.lines {
+      ret.push_str(&line);
+      ret.push('\n');
+    }
+    ret
+  }
+
+  fn indent(&self) -> String {
+    "  ".repeat(self.nodes.len())
+  }
+
+  fn extend_errors(&mut self) {
+    let (start, end) = self.cur;
+    let indent = self.indent();
+    let errors = std::mem::take(&mut self.no_range);
+    self.lines.extend(errors.into_iter().map(|e| format!("{indent}error@{start}..{end}: {e}")));
+  }
+
+  fn extend_ranged(&mut self) {
+    let indent = self.indent();
+    let errors = std::mem::take(&mut self.ranged);
+    self.lines.extend(
+      errors.into_iter().map(|(r, e)| format!("{indent}error@{}..{}: {e}", r.start, r.end)),
+    );
+  }
+}
+
+impl<K, E> Sink<K, E> for DebugSink
+where
+  K: fmt::Display + Triviable,
+  E: fmt::Display,
+{
+  fn enter(&mut self, kind: K) {
+    let line = format!("{}{kind}", self.indent());
+    self.nodes.push((self.lines.len(), self.cur.1));
+    self.lines.push(line);
+  }
+
+  fn token(&mut self, token: Token<'_, K>) {
+    let start = self.cur.1;
+    self.cur = (start, start + token.text.len());
+    self.extend_ranged();
+    if !token.kind.is_trivia() {
+      self.extend_errors();
+    }
+    let line =
+      format!("{}{}@{}..{} {:?}", self.indent(), token.kind, start, self.cur.1, token.text);
+    self.lines.push(line);
+  }
+
+  fn exit(&mut self) {
+    self.extend_ranged();
+    let (line, start) = self.nodes.pop().expect("exit with no nodes");
+    write!(self.lines[line], "@{start}..{}", self.cur.1).expect("write to String can't fail");
+  }
+
+  fn error(&mut self, error: E) {
+    self.no_range.push(error.to_string());
+  }
+
+  fn error_range(&mut self, error: E, range: Range<usize>) {
+    self.ranged.push((range, error.to_string()));
+  }
+}
+
+/// Checks that `actual` is the same as the contents of the file at `path`.
+///
+/// If the `UPDATE_EXPECT` environment variable is set, this instead writes `actual` to the file.
+///
+/// # Panics
+///
+/// If they were not the same, or the file could not be read or written.
+pub fn check_file<P>(actual: &str, path: P)
+where
+  P: AsRef<Path>,
+{
+  let path = path.as_ref();
+  if std::env::var_os("UPDATE_EXPECT").is_some() {
+    std::fs::write(path, actual).expect("couldn't write expected output");
+    return;
+  }
+  let expected = std::fs::read_to_string(path).expect("couldn't read expected output");
+  assert!(
+    expected == actual,
+    "{} did not match (set UPDATE_EXPECT=1 to update it)\n--- expected\n{expected}--- actual\n{actual}",
+    path.display()
+  );
+}
@@ -0,0 +1,314 @@
+//! Writing resolved events into a sink, which is shared by finishing a parser and replaying a log.
+
+use crate::Sink;
+use crate::event_log::Event;
+use crate::source::Source;
+use crate::trivia::{Attach, TriviaPolicy};
+use std::ops::Range;
+use token::{Token, Triviable};
+
+/// The state for writing events into a sink, kept between flushes.
+///
+/// Unless the trivia policy is eager, writing trivia is put off until the next non-trivia token, so
+/// the policy can know which nodes ended before the trivia and which start after it. So exiting
+/// nodes is put off too.
+#[derive(Debug)]
+pub(crate) struct Emitter<'a, K> {
+  /// The index of the next token to write.
+  tok_idx: usize,
+  /// How much of the next token was already written, because of splits.
+  split_offset: usize,
+  /// The nodes to enter before the next token, so empty nodes are never entered.
+  to_enter: Vec<K>,
+  /// The byte offsets where the nodes we entered in the sink start. The last `exits` of them were
+  /// exited, but not yet in the sink.
+  entered: Vec<usize>,
+  exits: usize,
+  /// How many nodes the parser is in.
+  levels: usize,
+  /// The byte offset after the last token.
+  offset: usize,
+  /// The kind of the first node.
+  fst: Option<K>,
+  /// Scratch space for a run of trivia.
+  run: Vec<Token<'a, K>>,
+}
+
+impl<K> Default for Emitter<'_, K> {
+  fn default() -> Self {
+    Self {
+      tok_idx: 0,
+      split_offset: 0,
+      to_enter: Vec::new(),
+      entered: Vec::new(),
+      exits: 0,
+      levels: 0,
+      offset: 0,
+      fst: None,
+      run: Vec::new(),
+    }
+  }
+}
+
+impl<'a, K> Emitter<'a, K>
+where
+  K: Copy + Triviable,
+{
+  /// Returns the index of the next token to write.
+  pub(crate) fn tok_idx(&self) -> usize {
+    self.tok_idx
+  }
+
+  /// Writes the event into the sink.
+  pub(crate) fn event<E>(
+    &mut self,
+    tokens: &mut Source<'a, K>,
+    sink: &mut dyn Sink<K, E>,
+    policy: &mut dyn TriviaPolicy<K>,
+    ev: Event<K, E>,
+  ) {
+    match ev {
+      Event::Enter(kind) => {
+        if self.fst.is_none() {
+          self.fst = Some(kind);
+        }
+        if self.levels != 0 {
+          self.eager_trivia(tokens, sink, policy);
+        }
+        self.to_enter.push(kind);
+        self.levels += 1;
+      }
+      Event::Exit => {
+        self.levels -= 1;
+        // trivia at the end goes in the root.
+        if self.levels == 0 {
+          self.eager_trivia(tokens, sink, policy);
+        }
+        if self.to_enter.pop().is_none() {
+          self.exits += 1;
+          if policy.eager() {
+            self.exit(self.exits, sink);
+          }
+        }
+        if self.levels == 1 {
+          self.eager_trivia(tokens, sink, policy);
+        }
+      }
+      Event::Token(remap) => {
+        self.trivia(tokens, sink, policy);
+        let mut tok = tokens.get(self.tok_idx).expect("token event with no token");
+        tok.text = &tok.text[self.split_offset..];
+        self.split_offset = 0;
+        if let Some(kind) = remap {
+          tok.kind = kind;
+        }
+        self.token(sink, tok);
+        self.tok_idx += 1;
+      }
+      Event::Split(kind, len) => {
+        self.trivia(tokens, sink, policy);
+        let tok = tokens.get(self.tok_idx).expect("split event with no token");
+        let offset = self.split_offset;
+        self.split_offset += len;
+        self.token(sink, Token { kind, text: &tok.text[offset..offset + len] });
+      }
+      Event::Error(error) => sink.error(error),
+      Event::ErrorTokens(error, n) => {
+        let range = self.tokens_range(tokens, n);
+        sink.error_range(error, range);
+      }
+      Event::ErrorNode(error) => {
+        let start = if self.to_enter.is_empty() {
+          self.entered[self.entered.len() - 1 - self.exits]
+        } else {
+          self.offset
+        };
+        sink.error_range(error, start..self.offset);
+      }
+    }
+  }
+
+  /// Finishes writing into the sink.
+  ///
+  /// # Panics
+  ///
+  /// If not all nodes were exited.
+  pub(crate) fn finish<E>(
+    &mut self,
+    tokens: &mut Source<'a, K>,
+    sink: &mut dyn Sink<K, E>,
+    policy: &mut dyn TriviaPolicy<K>,
+  ) {
+    if !policy.eager() {
+      self.trivia(tokens, sink, policy);
+    }
+    self.exit(self.exits, sink);
+    assert!(self.entered.is_empty() && self.to_enter.is_empty());
+    // give a node for the empty parse, with any trivia in it
+    if self.tok_idx == 0
+      && let Some(fst) = self.fst
+    {
+      sink.enter(fst);
+      while let Some(tok) = tokens.get(self.tok_idx)
+        && tok.kind.is_trivia()
+      {
+        self.write(sink, tok);
+        self.tok_idx += 1;
+      }
+      sink.exit();
+    }
+  }
+
+  /// Writes the run of trivia starting at the next token into the current node, if the policy is
+  /// eager.
+  fn eager_trivia<E>(
+    &mut self,
+    tokens: &mut Source<'a, K>,
+    sink: &mut dyn Sink<K, E>,
+    policy: &mut dyn TriviaPolicy<K>,
+  ) {
+    if policy.eager() {
+      self.trivia(tokens, sink, policy);
+    }
+  }
+
+  /// Writes the run of trivia starting at the next token, where the policy says.
+  fn trivia<E>(
+    &mut self,
+    tokens: &mut Source<'a, K>,
+    sink: &mut dyn Sink<K, E>,
+    policy: &mut dyn TriviaPolicy<K>,
+  ) {
+    // the next token was partially written, so it's not trivia.
+    if self.split_offset != 0 {
+      return;
+    }
+    if policy.eager() {
+      while let Some(tok) = tokens.get(self.tok_idx)
+        && tok.kind.is_trivia()
+      {
+        self.token(sink, tok);
+        self.tok_idx += 1;
+      }
+      return;
+    }
+    let mut run = std::mem::take(&mut self.run);
+    run.clear();
+    while let Some(tok) = tokens.get(self.tok_idx + run.len())
+      && tok.kind.is_trivia()
+    {
+      run.push(tok);
+    }
+    let mut attach = Attach::Previous;
+    let mut entered_next = false;
+    for (idx, &tok) in run.iter().enumerate() {
+      attach = attach.max(policy.attach(&run, idx));
+      if !self.move_to(attach, &mut entered_next, sink) {
+        break;
+      }
+      self.write(sink, tok);
+      self.tok_idx += 1;
+    }
+    self.run = run;
+  }
+
+  /// Exits and enters nodes to get to where trivia should go. Returns whether there was such a
+  /// place.
+  ///
+  /// `entered_next` is whether the outermost node that starts after the run was already entered for
+  /// it, so that only that one node is entered, and not the nodes inside it too.
+  fn move_to<E>(
+    &mut self,
+    attach: Attach,
+    entered_next: &mut bool,
+    sink: &mut dyn Sink<K, E>,
+  ) -> bool {
+    if *entered_next {
+      return true;
+    }
+    // at the very end, the root was exited too, but the trivia still goes in it.
+    let root =
+      usize::from(self.exits != 0 && self.entered.len() == self.exits && self.to_enter.is_empty());
+    let ended = self.exits - root;
+    let attach = match attach {
+      Attach::Previous if ended == 0 => Attach::Parent,
+      Attach::Next if self.to_enter.is_empty() => Attach::Parent,
+      attach => attach,
+    };
+    // there's no parent at the very start.
+    let attach = match attach {
+      Attach::Parent if self.entered.len() == ended => {
+        if self.to_enter.is_empty() {
+          return false;
+        }
+        Attach::Next
+      }
+      attach => attach,
+    };
+    match attach {
+      Attach::Previous => self.exit(ended - 1, sink),
+      Attach::Parent => self.exit(ended, sink),
+      Attach::Next => {
+        self.exit(ended, sink);
+        let kind = self.to_enter.remove(0);
+        self.enter(kind, sink);
+        *entered_next = true;
+      }
+    }
+    true
+  }
+
+  fn enter<E>(&mut self, kind: K, sink: &mut dyn Sink<K, E>) {
+    sink.enter(kind);
+    self.entered.push(self.offset);
+  }
+
+  /// Exits `n` of the nodes which were exited but not yet in the sink.
+  fn exit<E>(&mut self, n: usize, sink: &mut dyn Sink<K, E>) {
+    for _ in 0..n {
+      sink.exit();
+      self.entered.pop();
+      self.exits -= 1;
+    }
+  }
+
+  /// Exits and enters the nodes that were exited and entered before the non-trivia token, then
+  /// adds it.
+  fn token<E>(&mut self, sink: &mut dyn Sink<K, E>, tok: Token<'_, K>) {
+    self.exit(self.exits, sink);
+    let mut to_enter = std::mem::take(&mut self.to_enter);
+    for kind in to_enter.drain(..) {
+      self.enter(kind, sink);
+    }
+    self.to_enter = to_enter;
+    self.write(sink, tok);
+  }
+
+  fn write<E>(&mut self, sink: &mut dyn Sink<K, E>, tok: Token<'_, K>) {
+    self.offset += tok.text.len();
+    sink.token(tok);
+  }
+
+  /// Returns the range of the `n` non-trivia tokens starting at the next token to write.
+  fn tokens_range(&self, tokens: &mut Source<'_, K>, n: usize) -> Range<usize> {
+    let mut ret = self.offset..self.offset;
+    let mut pos = self.offset;
+    let mut left = n;
+    let mut idx = self.tok_idx;
+    while left != 0
+      && let Some(tok) = tokens.get(idx)
+    {
+      let len = tok.text.len() - if idx == self.tok_idx { self.split_offset } else { 0 };
+      if !tok.kind.is_trivia() {
+        if left == n {
+          ret.start = pos;
+        }
+        ret.end = pos + len;
+        left -= 1;
+      }
+      pos += len;
+      idx += 1;
+    }
+    ret
+  }
+}
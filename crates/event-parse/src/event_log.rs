@@ -0,0 +1,262 @@
+//! Logs of the events from a parse, which may be replayed into sinks later and serialized.
+//!
+//! Get a log with [`Parser::into_events`](crate::Parser::into_events). Replaying it with
+//! [`replay`] or [`into_replay`] against the same tokens writes the same tree into a sink as
+//! [`Parser::finish`](crate::Parser::finish) would have, including where trivia goes. This is useful
+//! for e.g. caching parses on disk, or diffing the events from different versions of a grammar.
+
+use crate::Sink;
+use crate::emit::Emitter;
+use crate::source::Source;
+use crate::trivia::{DefaultPolicy, TriviaPolicy};
+use token::{Token, Triviable};
+
+/// An event from a parse.
+///
+/// Unlike the events the parser keeps internally, these are in the order they are written into the
+/// sink: e.g. nodes made with [`Parser::precede`](crate::Parser::precede) are entered before the
+/// nodes they precede.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Event<K, E> {
+  /// Enter a node of the kind.
+  Enter(K),
+  /// Add the next token, with the new kind if there is one.
+  Token(Option<K>),
+  /// Add the given number of bytes of the next token as a token of the kind, leaving the rest of
+  /// the token as the next token.
+  Split(K, usize),
+  /// Exit the current node.
+  Exit,
+  /// An error at the next non-trivia token.
+  Error(E),
+  /// An error for the given number of non-trivia tokens starting at the next one.
+  ErrorTokens(E, usize),
+  /// An error for the node about to be exited.
+  ErrorNode(E),
+}
+
+/// Writes the `events` for the `tokens` into the `sink`.
+///
+/// # Panics
+///
+/// If the events are not valid for the tokens, as in e.g. if they are not from a parse of the
+/// tokens.
+pub fn replay<K, E>(events: &[Event<K, E>], tokens: &[Token<'_, K>], sink: &mut dyn Sink<K, E>)
+where
+  K: Copy + Triviable,
+  E: Clone,
+{
+  replay_with(events, tokens, sink, &mut DefaultPolicy);
+}
+
+/// Like [`replay`], but with a policy for where trivia goes, as with
+/// [`Parser::finish_with`](crate::Parser::finish_with).
+///
+/// # Panics
+///
+/// As with [`replay`].
+pub fn replay_with<K, E>(
+  events: &[Event<K, E>],
+  tokens: &[Token<'_, K>],
+  sink: &mut dyn Sink<K, E>,
+  policy: &mut dyn TriviaPolicy<K>,
+) where
+  K: Copy + Triviable,
+  E: Clone,
+{
+  replay_iter(events.iter().cloned(), tokens, sink, policy);
+}
+
+/// Like [`replay`], but takes the `events`, so they are not cloned.
+///
+/// # Panics
+///
+/// As with [`replay`].
+pub fn into_replay<K, E>(
+  events: Vec<Event<K, E>>,
+  tokens: &[Token<'_, K>],
+  sink: &mut dyn Sink<K, E>,
+) where
+  K: Copy + Triviable,
+{
+  into_replay_with(events, tokens, sink, &mut DefaultPolicy);
+}
+
+/// Like [`replay_with`], but takes the `events`, so they are not cloned.
+///
+/// # Panics
+///
+/// As with [`replay`].
+pub fn into_replay_with<K, E>(
+  events: Vec<Event<K, E>>,
+  tokens: &[Token<'_, K>],
+  sink: &mut dyn Sink<K, E>,
+  policy: &mut dyn TriviaPolicy<K>,
+) where
+  K: Copy + Triviable,
+{
+  replay_iter(events, tokens, sink, policy);
+}
+
+fn replay_iter<K, E, I>(
+  events: I,
+  tokens: &[Token<'_, K>],
+  sink: &mut dyn Sink<K, E>,
+  policy: &mut dyn TriviaPolicy<K>,
+) where
+  K: Copy + Triviable,
+  I: IntoIterator<Item = Event<K, E>>,
+{
+  let mut tokens = Source::Slice(tokens);
+  let mut emitter = Emitter::default();
+  for ev in events {
+    emitter.event(&mut tokens, sink, policy, ev);
+  }
+  emitter.finish(&mut tokens, sink, policy);
+}
+
+/// Types which can be written to and read from bytes, for serializing event logs.
+pub trait Encode: Sized {
+  /// Writes the value to the end of `buf`.
+  fn encode(&self, buf: &mut Vec<u8>);
+
+  /// Reads a value from the start of `bytes`, then advances `bytes` past it. Returns `None` if the
+  /// bytes did not start with a valid value.
+  fn decode(bytes: &mut &[u8]) -> Option<Self>;
+}
+
+// LEB128, so smaller numbers use fewer bytes.
+impl Encode for u64 {
+  fn encode(&self, buf: &mut Vec<u8>) {
+    let mut n = *self;
+    while n >= 0x80 {
+      #[allow(clippy::cast_possible_truncation)]
+      buf.push((n as u8) | 0x80);
+      n >>= 7;
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    buf.push(n as u8);
+  }
+
+  fn decode(bytes: &mut &[u8]) -> Option<Self> {
+    let mut ret = 0u64;
+    let mut shift = 0u32;
+    loop {
+      let (&b, tail) = bytes.split_first()?;
+      *bytes = tail;
+      // only the lowest bit of the tenth byte fits.
+      if shift == 63 && b & 0x7e != 0 {
+        return None;
+      }
+      ret |= u64::from(b & 0x7f).checked_shl(shift)?;
+      if b & 0x80 == 0 {
+        return Some(ret);
+      }
+      shift += 7;
+    }
+  }
+}
+
+macro_rules! encode_via_u64 {
+  ($($t:ty),*) => {
+    $(
+      impl Encode for $t {
+        fn encode(&self, buf: &mut Vec<u8>) {
+          u64::try_from(*self).expect("integer too large").encode(buf);
+        }
+
+        fn decode(bytes: &mut &[u8]) -> Option<Self> {
+          u64::decode(bytes).and_then(|n| Self::try_from(n).ok())
+        }
+      }
+    )*
+  };
+}
+
+encode_via_u64!(u8, u16, u32, usize);
+
+/// The version of the serialized form, which is the first byte of it.
+const VERSION: u8 = 0;
+
+const ENTER: u8 = 0;
+const TOKEN: u8 = 1;
+const TOKEN_REMAP: u8 = 2;
+const SPLIT: u8 = 3;
+const EXIT: u8 = 4;
+const ERROR: u8 = 5;
+const ERROR_TOKENS: u8 = 6;
+const ERROR_NODE: u8 = 7;
+
+/// Serializes the `events`.
+#[must_use]
+pub fn encode<K, E>(events: &[Event<K, E>]) -> Vec<u8>
+where
+  K: Encode,
+  E: Encode,
+{
+  let mut buf = vec![VERSION];
+  for ev in events {
+    match ev {
+      Event::Enter(kind) => {
+        buf.push(ENTER);
+        kind.encode(&mut buf);
+      }
+      Event::Token(None) => buf.push(TOKEN),
+      Event::Token(Some(kind)) => {
+        buf.push(TOKEN_REMAP);
+        kind.encode(&mut buf);
+      }
+      Event::Split(kind, len) => {
+        buf.push(SPLIT);
+        kind.encode(&mut buf);
+        len.encode(&mut buf);
+      }
+      Event::Exit => buf.push(EXIT),
+      Event::Error(error) => {
+        buf.push(ERROR);
+        error.encode(&mut buf);
+      }
+      Event::ErrorTokens(error, n) => {
+        buf.push(ERROR_TOKENS);
+        error.encode(&mut buf);
+        n.encode(&mut buf);
+      }
+      Event::ErrorNode(error) => {
+        buf.push(ERROR_NODE);
+        error.encode(&mut buf);
+      }
+    }
+  }
+  buf
+}
+
+/// Deserializes events serialized with [`encode`]. Returns `None` if the bytes were not valid,
+/// e.g. if they were from a different version of this library.
+#[must_use]
+pub fn decode<K, E>(mut bytes: &[u8]) -> Option<Vec<Event<K, E>>>
+where
+  K: Encode,
+  E: Encode,
+{
+  let bytes = &mut bytes;
+  if u8::decode(bytes)? != VERSION {
+    return None;
+  }
+  let mut ret = Vec::new();
+  while let Some((&tag, tail)) = bytes.split_first() {
+    *bytes = tail;
+    let ev = match tag {
+      ENTER => Event::Enter(K::decode(bytes)?),
+      TOKEN => Event::Token(None),
+      TOKEN_REMAP => Event::Token(Some(K::decode(bytes)?)),
+      SPLIT => Event::Split(K::decode(bytes)?, usize::decode(bytes)?),
+      EXIT => Event::Exit,
+      ERROR => Event::Error(E::decode(bytes)?),
+      ERROR_TOKENS => Event::ErrorTokens(E::decode(bytes)?, usize::decode(bytes)?),
+      ERROR_NODE => Event::ErrorNode(E::decode(bytes)?),
+      _ => return None,
+    };
+    ret.push(ev);
+  }
+  Some(ret)
+}
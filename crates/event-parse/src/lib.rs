@@ -10,7 +10,8 @@
 //!    - [`Triviable`]
 //!    - [`fmt::Display`]
 //! 3. Define a lexer which transforms an a string into a vector of
-//!    contiguous [`Token`]s using this `SyntaxKind`.
+//!    contiguous [`Token`]s using this `SyntaxKind`. (Or an iterator of
+//!    them, with [`Parser::new_streaming`].)
 //! 4. Define your language's grammar with functions operating on a [`Parser`].
 //! 5. Call [`Parser::finish`] when done, and feed it a suitable [`Sink`] for
 //!    the collected parsing events.
@@ -19,25 +20,148 @@
 //!
 //! [1]: https://github.com/rust-analyzer/rust-analyzer
 
+#[cfg(feature = "cst")]
+pub mod cst;
+pub mod debug_sink;
+pub mod event_log;
+pub mod pratt;
+#[cfg(feature = "rowan")]
+pub mod reparse;
 #[cfg(feature = "rowan")]
 pub mod rowan_sink;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod trivia;
+
+mod emit;
+mod source;
+#[cfg(test)]
+mod tests;
 
+use always::always;
 use drop_bomb::DropBomb;
+use emit::Emitter;
+use source::Source;
+use std::collections::VecDeque;
 use std::fmt;
+use std::ops::Range;
+use std::sync::{Arc, Weak};
 use token::{Token, Triviable};
+use trivia::TriviaPolicy;
 
 /// A event-based parser.
 #[derive(Debug)]
 pub struct Parser<'a, K, E> {
-  tokens: &'a [Token<'a, K>],
+  tokens: Source<'a, K>,
   tok_idx: usize,
+  max_lookahead: usize,
   events: Vec<Option<Event<K, E>>>,
+  /// The index of the first event in `events`. Events before it were flushed.
+  ev_base: usize,
+  /// For each open node from [`Parser::enter`] or [`Parser::precede`], the index of the first event
+  /// that must be written together with the node's event.
+  open: Vec<usize>,
+  emitter: Emitter<'a, K>,
+  /// Whether each save not yet used is still alive, and the number of events and the token index at
+  /// it. Saves that were dropped are not alive, and are ignored.
+  saves: Vec<(Weak<()>, usize, usize)>,
+  expected: Vec<K>,
+  recorded: Option<(usize, Vec<K>)>,
+  /// One more than the number of looks left, so that running out is reported once, at the first
+  /// look with none left.
+  fuel: u32,
+  /// What `fuel` is reset to when a token is consumed.
+  fuel_limit: u32,
+  split: Option<Split>,
+  #[cfg(feature = "stats")]
+  stats: stats::Recorder<K>,
 }
 
+/// The default for [`Parser::set_fuel`].
+pub const DEFAULT_FUEL: u32 = 256;
+
 impl<'a, K, E> Parser<'a, K, E> {
   /// Returns a new parser for the given tokens.
   pub fn new(tokens: &'a [Token<'a, K>]) -> Self {
-    Self { tokens, tok_idx: 0, events: Vec::new() }
+    Self::with_source(Source::Slice(tokens), usize::MAX)
+  }
+
+  /// Returns a new parser which pulls tokens from `tokens` as they are needed, instead of needing
+  /// all of them up front.
+  ///
+  /// [`Parser::peek_n`] may look at most `max_lookahead` tokens ahead. Together with
+  /// [`Parser::flush`], this bounds how many tokens are kept in memory at once.
+  ///
+  /// The iterator must be `Send`, so that all parsers are `Send`.
+  pub fn new_streaming<I>(tokens: I, max_lookahead: usize) -> Self
+  where
+    I: IntoIterator<Item = Token<'a, K>>,
+    I::IntoIter: Send + 'a,
+  {
+    let iter = Box::new(tokens.into_iter().fuse());
+    let source = Source::Iter { buf: VecDeque::new(), base: 0, iter };
+    Self::with_source(source, max_lookahead)
+  }
+
+  fn with_source(tokens: Source<'a, K>, max_lookahead: usize) -> Self {
+    Self {
+      tokens,
+      tok_idx: 0,
+      max_lookahead,
+      events: Vec::new(),
+      ev_base: 0,
+      open: Vec::new(),
+      emitter: Emitter::default(),
+      saves: Vec::new(),
+      expected: Vec::new(),
+      recorded: None,
+      fuel: DEFAULT_FUEL + 1,
+      fuel_limit: DEFAULT_FUEL + 1,
+      split: None,
+      #[cfg(feature = "stats")]
+      stats: stats::Recorder::default(),
+    }
+  }
+
+  /// Sets the "fuel" for the parser, which is how many times the parser may look at the current
+  /// token with e.g. [`Parser::peek`] or [`Parser::at`] without consuming it. Consuming a token
+  /// refuels the parser.
+  ///
+  /// Running out of fuel usually means there's a bug in the grammar that makes it loop forever
+  /// without consuming tokens. When that happens, we panic in debug mode. In release mode, we log
+  /// an error and the parser then acts as if there are no more tokens, which should end most such
+  /// loops.
+  ///
+  /// # Panics
+  ///
+  /// If `fuel` is 0, since then the parser could never look at any token.
+  pub fn set_fuel(&mut self, fuel: u32) {
+    assert!(fuel != 0, "fuel must not be 0");
+    self.fuel = fuel.saturating_add(1);
+    self.fuel_limit = self.fuel;
+  }
+
+  /// Returns the kinds tested with [`Parser::at`] at the current token since the last bump, most
+  /// recently tested last.
+  #[must_use]
+  pub fn expected(&self) -> &[K] {
+    &self.expected
+  }
+
+  /// Starts recording every kind tested with [`Parser::at`] when the current token is the token at
+  /// `idx` in the tokens given to [`Parser::new`]. Use `idx == tokens.len()` for the end of input.
+  /// If the token at `idx` is trivia, the next token that isn't is used instead, since the current
+  /// token is never trivia.
+  ///
+  /// This is useful for e.g. keyword completion at the token under a cursor.
+  pub fn record_expected_at(&mut self, idx: usize) {
+    self.recorded = Some((idx, Vec::new()));
+  }
+
+  /// Returns the kinds recorded because of [`Parser::record_expected_at`].
+  #[must_use]
+  pub fn recorded_expected(&self) -> &[K] {
+    self.recorded.as_ref().map_or(&[], |(_, kinds)| kinds.as_slice())
   }
 
   /// Starts parsing a syntax construct.
@@ -60,9 +184,33 @@ impl<'a, K, E> Parser<'a, K, E> {
   ///
   /// then Weird Things might happen.
   pub fn enter(&mut self) -> Entered {
-    let ev_idx = self.events.len();
-    self.events.push(None);
-    Entered { bomb: DropBomb::new("Entered markers must be exited"), ev_idx, tok_idx: self.tok_idx }
+    let ev_idx = self.events_len();
+    self.push(None);
+    self.open.push(ev_idx);
+    #[cfg(feature = "stats")]
+    self.stats.enter(ev_idx);
+    self.entered(ev_idx, ev_idx, false)
+  }
+
+  /// Starts parsing a syntax construct whose kind is already known, like [`Parser::enter`].
+  ///
+  /// Unlike with `enter`, events after this may be written by [`Parser::flush`] before the
+  /// construct is exited. This is useful for e.g. the root of a large file. The kind given to
+  /// [`Parser::exit`] for the returned [`Entered`] is ignored.
+  ///
+  /// Since the node may already be written when it is done, the returned `Entered` may only be
+  /// passed to [`Parser::abandon`] if there was no flush since this call.
+  pub fn enter_with(&mut self, kind: K) -> Entered {
+    let ev_idx = self.events_len();
+    self.push(Some(Event::Enter(kind, None)));
+    #[cfg(feature = "stats")]
+    self.stats.enter(ev_idx);
+    self.entered(ev_idx, ev_idx, true)
+  }
+
+  fn entered(&self, ev_idx: usize, chain: usize, known: bool) -> Entered {
+    let bomb = DropBomb::new("Entered markers must be exited");
+    Entered { bomb, ev_idx, tok_idx: self.tok_idx, chain, known }
   }
 
   /// Abandons parsing a syntax construct.
@@ -72,10 +220,20 @@ impl<'a, K, E> Parser<'a, K, E> {
   ///
   /// # Panics
   ///
-  /// On internal error.
+  /// If the `Entered` is from [`Parser::enter_with`] and there was a [`Parser::flush`] since then,
+  /// since the node was already written. Also on internal error.
   pub fn abandon(&mut self, mut en: Entered) {
     en.bomb.defuse();
-    assert!(self.events[en.ev_idx].is_none());
+    self.check_save(en.ev_idx);
+    #[cfg(feature = "stats")]
+    self.stats.abandon();
+    let ev = self.event(en.ev_idx);
+    if en.known {
+      *ev = None;
+    } else {
+      assert!(ev.is_none());
+      self.close(en.chain);
+    }
   }
 
   /// Finishes parsing a syntax construct.
@@ -85,11 +243,31 @@ impl<'a, K, E> Parser<'a, K, E> {
   /// On internal error.
   pub fn exit(&mut self, mut en: Entered, kind: K) -> Exited {
     en.bomb.defuse();
-    let ev = &mut self.events[en.ev_idx];
-    assert!(ev.is_none());
-    *ev = Some(Event::Enter(kind, None));
-    self.events.push(Some(Event::Exit));
-    Exited { ev_idx: en.ev_idx, is_empty: self.tok_idx == en.tok_idx }
+    self.check_save(en.ev_idx);
+    #[cfg(feature = "stats")]
+    self.stats.exit();
+    if !en.known {
+      let ev = self.event(en.ev_idx);
+      assert!(ev.is_none());
+      *ev = Some(Event::Enter(kind, None));
+      self.close(en.chain);
+    }
+    self.push(Some(Event::Exit));
+    Exited { ev_idx: en.ev_idx, chain: en.chain, is_empty: self.tok_idx == en.tok_idx }
+  }
+
+  /// Finishes parsing a syntax construct like [`Parser::exit`], and records an error for the whole
+  /// construct.
+  ///
+  /// The range of the error starts at the first token of the construct, and ends at the last
+  /// non-trivia token consumed before this.
+  ///
+  /// # Panics
+  ///
+  /// On internal error.
+  pub fn exit_with_error(&mut self, en: Entered, kind: K, error: E) -> Exited {
+    self.push(Some(Event::Error(error, ErrorSpan::Node)));
+    self.exit(en, kind)
   }
 
   /// Starts parsing a syntax construct and makes it the parent of the given
@@ -102,28 +280,37 @@ impl<'a, K, E> Parser<'a, K, E> {
   ///
   /// # Panics
   ///
-  /// On internal error.
+  /// If the completed node was written by [`Parser::flush`], or on internal error.
   pub fn precede(&mut self, ex: Exited) -> Entered {
-    let ret = self.enter();
-    match self.events[ex.ev_idx] {
-      Some(Event::Enter(_, ref mut parent)) => {
+    self.check_save(ex.ev_idx);
+    let ev_idx = self.events_len();
+    match self.event(ex.ev_idx) {
+      Some(Event::Enter(_, parent)) => {
         assert!(parent.is_none());
-        *parent = Some(ret.ev_idx);
+        *parent = Some(ev_idx);
       }
-      ref ev => unreachable!("{:?} preceded {:?}, not Enter", ex, ev),
+      ev => unreachable!("{:?} preceded {:?}, not Enter", ex, ev),
     }
-    ret
+    self.push(None);
+    self.open.push(ex.chain);
+    #[cfg(feature = "stats")]
+    self.stats.enter(ev_idx);
+    self.entered(ev_idx, ex.chain, false)
   }
 
   /// Save the state of the parser.
   ///
-  /// Use it with `ok_since` to implement unbounded backtracking.
+  /// Use it with `ok_since` to implement unbounded backtracking, or with
+  /// `restore` to rewind for any reason. The returned [`Save`] should be passed to
+  /// one of them. Dropping it instead keeps everything since the save, as with an
+  /// `ok_since` that returned `true`.
   ///
   /// For any `Entered` or `Exited` that were created before the save, do not
   /// `exit` or `precede` them respectively between the save and the `ok_since`.
   /// Or do anything else that modifies any events before the save. Otherwise
   /// the parser won't fully recover to its original state before the save, and
-  /// weird things will probably happen.
+  /// weird things will probably happen. In debug builds, we panic when we
+  /// detect this.
   ///
   /// It's intended to be used like this:
   ///
@@ -140,22 +327,136 @@ impl<'a, K, E> Parser<'a, K, E> {
   ///   // the parser is reset to the state at the save
   /// }
   /// ```
-  pub fn save(&self) -> Save {
-    Save { tok_idx: self.tok_idx, events_len: self.events.len() }
+  pub fn save(&mut self) -> Save {
+    let events_len = self.events_len();
+    let alive = Arc::new(());
+    self.saves.push((Arc::downgrade(&alive), events_len, self.tok_idx));
+    Save { alive, tok_idx: self.tok_idx, events_len, split: self.split }
   }
 
   /// Returns whether there were _no_ errors since the save, i.e. whether we did
   /// _not_ restore to that save.
-  #[allow(clippy::needless_pass_by_value)]
+  ///
+  /// # Panics
+  ///
+  /// On internal error.
   pub fn ok_since(&mut self, save: Save) -> bool {
+    let since = save.events_len - self.ev_base;
     let error_since =
-      self.events.iter().skip(save.events_len).any(|ev| matches!(*ev, Some(Event::Error(..))));
+      self.events.iter().skip(since).any(|ev| matches!(*ev, Some(Event::Error(..))));
     if error_since {
-      self.tok_idx = save.tok_idx;
-      self.events.truncate(save.events_len);
+      self.restore(save);
+    } else {
+      self.forget(save);
     }
     !error_since
   }
+
+  /// Restores the parser to the state at the save, regardless of whether there were errors.
+  ///
+  /// # Panics
+  ///
+  /// On internal error.
+  pub fn restore(&mut self, save: Save) {
+    let since = save.events_len - self.ev_base;
+    // the expected kinds are for a token after the save.
+    if self.events.iter().skip(since).any(|ev| matches!(*ev, Some(Event::Token(_)))) {
+      self.expected.clear();
+    }
+    self.tok_idx = save.tok_idx;
+    #[cfg(feature = "stats")]
+    for (idx, ev) in self.events.drain(since..).enumerate() {
+      if let Some(Event::Enter(kind, _)) = ev {
+        self.stats.resolve(self.ev_base + since + idx, kind);
+      }
+    }
+    #[cfg(not(feature = "stats"))]
+    self.events.truncate(since);
+    self.split = save.split;
+    self.forget(save);
+    #[cfg(feature = "stats")]
+    self.stats.backtrack();
+  }
+
+  /// Runs `f`, and if it returns `None`, restores the parser to the state before `f` ran. Returns
+  /// what `f` returned.
+  ///
+  /// Unlike with [`Parser::ok_since`], errors recorded by `f` don't make us restore, and are kept if
+  /// `f` returned `Some`. Any `Entered` made by `f` must still be exited or abandoned, even if it
+  /// returns `None`.
+  pub fn try_parse<T, F>(&mut self, f: F) -> Option<T>
+  where
+    F: FnOnce(&mut Self) -> Option<T>,
+  {
+    let save = self.save();
+    let ret = f(self);
+    if ret.is_some() {
+      self.forget(save);
+    } else {
+      self.restore(save);
+    }
+    ret
+  }
+
+  /// Stops tracking the save.
+  #[allow(clippy::needless_pass_by_value)]
+  fn forget(&mut self, save: Save) {
+    self.saves.retain(|(alive, _, _)| alive.strong_count() != 0);
+    let last = self.saves.pop();
+    debug_assert!(
+      last.is_some_and(|(alive, _, _)| alive.ptr_eq(&Arc::downgrade(&save.alive))),
+      "saves must be used in LIFO order"
+    );
+  }
+
+  /// Checks that the event at `ev_idx` may be changed given the active saves.
+  fn check_save(&self, ev_idx: usize) {
+    debug_assert!(
+      self
+        .saves
+        .iter()
+        .rfind(|(alive, _, _)| alive.strong_count() != 0)
+        .is_none_or(|&(_, events_len, _)| events_len <= ev_idx),
+      "changed a marker from before the latest save"
+    );
+  }
+
+  /// Returns statistics about the parse so far.
+  #[cfg(feature = "stats")]
+  #[must_use]
+  pub fn stats(&self) -> stats::Stats<K>
+  where
+    K: Copy + Eq + std::hash::Hash,
+  {
+    self.stats.get(|idx| match self.events[idx - self.ev_base] {
+      Some(Event::Enter(kind, _)) => kind,
+      ref ev => unreachable!("{idx} was {ev:?}, not Enter"),
+    })
+  }
+
+  fn push(&mut self, ev: Option<Event<K, E>>) {
+    #[cfg(feature = "stats")]
+    self.stats.event();
+    self.events.push(ev);
+  }
+
+  /// Returns the number of events, including flushed ones.
+  fn events_len(&self) -> usize {
+    self.ev_base + self.events.len()
+  }
+
+  /// Returns the event at `idx`.
+  fn event(&mut self, idx: usize) -> &mut Option<Event<K, E>> {
+    let idx = idx.checked_sub(self.ev_base).expect("marker was already flushed");
+    &mut self.events[idx]
+  }
+
+  /// Marks the node with this chain start as no longer open.
+  fn close(&mut self, chain: usize) {
+    if let Some(idx) = self.open.iter().rposition(|&c| c == chain) {
+      self.open.remove(idx);
+    }
+  }
 }
 
 impl<'a, K, E> Parser<'a, K, E>
@@ -167,28 +468,64 @@ where
   ///
   /// Equivalent to `self.peek_n(0)`. See [`Parser::peek_n`].
   pub fn peek(&mut self) -> Option<Token<'a, K>> {
-    while let Some(&tok) = self.tokens.get(self.tok_idx) {
+    if self.use_fuel() { self.peek_raw() } else { None }
+  }
+
+  fn peek_raw(&mut self) -> Option<Token<'a, K>> {
+    while let Some(tok) = self.tokens.get(self.tok_idx) {
       if tok.kind.is_trivia() {
+        if let Some((idx, _)) = &mut self.recorded
+          && *idx == self.tok_idx
+        {
+          *idx += 1;
+        }
         self.tok_idx += 1;
       } else {
-        return Some(tok);
+        return Some(self.split_rest(tok));
       }
     }
     None
   }
 
+  /// Returns what's left of the token if it is the current token and it was partially consumed.
+  fn split_rest(&self, tok: Token<'a, K>) -> Token<'a, K> {
+    match self.split {
+      Some(split) if split.tok_idx == self.tok_idx => {
+        match self.events[split.ev_idx - self.ev_base] {
+          Some(Event::Split(_, rest, _)) => Token { kind: rest, text: &tok.text[split.offset..] },
+          ref ev => unreachable!("{:?} was {:?}, not Split", split, ev),
+        }
+      }
+      _ => tok,
+    }
+  }
+
   /// Returns the token `n` tokens in front of the current token, or `None` if
   /// there is no such token.
   ///
   /// The current token is the first token not yet consumed for which
   /// [`Triviable::is_trivia`] returns `true`; thus, if this returns
   /// `Some(tok)`, then `tok.kind.is_trivia()` is `false`.
+  ///
+  /// # Panics
+  ///
+  /// If `n` is more than the max lookahead given to [`Parser::new_streaming`].
   pub fn peek_n(&mut self, n: usize) -> Option<Token<'a, K>> {
-    let mut ret = self.peek();
+    assert!(
+      n <= self.max_lookahead,
+      "cannot peek {n} tokens ahead, the max is {}",
+      self.max_lookahead
+    );
+    if !self.use_fuel() {
+      return None;
+    }
+    #[cfg(feature = "stats")]
+    self.stats.peek(n);
+    let mut ret = self.peek_raw();
     let old_tok_idx = self.tok_idx;
     for _ in 0..n {
       self.tok_idx += 1;
-      ret = self.peek();
+      ret = self.peek_raw();
     }
     self.tok_idx = old_tok_idx;
     ret
@@ -206,99 +543,262 @@ where
   ///
   /// If there was no token to bump.
   pub fn bump(&mut self) -> Token<'a, K> {
-    let ret = self.peek().expect("bump with no tokens");
-    self.events.push(Some(Event::Token));
+    self.bump_impl(None)
+  }
+
+  /// Consumes the current token like [`Parser::bump`], but gives it the syntax kind `kind` in the
+  /// tree instead of the kind it had from the lexer. Returns the token with the new kind.
+  ///
+  /// This is useful for contextual keywords, which are lexed as e.g. identifiers.
+  ///
+  /// # Panics
+  ///
+  /// If there was no token to bump.
+  pub fn bump_remap(&mut self, kind: K) -> Token<'a, K> {
+    self.bump_impl(Some(kind))
+  }
+
+  fn bump_impl(&mut self, mut remap: Option<K>) -> Token<'a, K> {
+    let mut ret = self.peek_raw().expect("bump with no tokens");
+    if self.split.take().is_some_and(|split| split.tok_idx == self.tok_idx) {
+      remap = remap.or(Some(ret.kind));
+    }
+    self.push(Some(Event::Token(remap)));
     self.tok_idx += 1;
+    self.expected.clear();
+    self.fuel = self.fuel_limit;
+    if let Some(kind) = remap {
+      ret.kind = kind;
+    }
     ret
   }
 
+  /// Consumes the first `len` bytes of the current token as a token of kind `kind`, and leaves the
+  /// rest of it as the current token, with kind `rest`. Returns the consumed token.
+  ///
+  /// This is useful for when e.g. `>>` was lexed as one token, but in some contexts it should be two
+  /// `>` tokens.
+  ///
+  /// # Panics
+  ///
+  /// If there was no token to bump, or if `len` would make either of the two resulting tokens empty
+  /// or not be at a char boundary.
+  pub fn bump_split(&mut self, len: usize, kind: K, rest: K) -> Token<'a, K> {
+    let tok = self.peek_raw().expect("bump_split with no tokens");
+    assert!(
+      0 < len && len < tok.text.len() && tok.text.is_char_boundary(len),
+      "cannot split {:?} at {len}",
+      tok.text
+    );
+    let offset = match self.split {
+      Some(split) if split.tok_idx == self.tok_idx => split.offset,
+      _ => 0,
+    };
+    let ev_idx = self.events_len();
+    self.push(Some(Event::Split(kind, rest, len)));
+    self.split = Some(Split { tok_idx: self.tok_idx, offset: offset + len, ev_idx });
+    self.expected.clear();
+    self.fuel = self.fuel_limit;
+    Token { kind, text: &tok.text[..len] }
+  }
+
+  /// Returns whether the current token has the given `text`, regardless of its kind.
+  ///
+  /// Use this with [`Parser::bump_remap`] for contextual keywords.
+  pub fn at_contextual(&mut self, text: &str) -> bool {
+    self.peek().is_some_and(|tok| tok.text == text)
+  }
+
+  /// Adds the kind to the [expected kinds](Parser::expected) at the current token, without looking
+  /// at it.
+  fn add_expected(&mut self, kind: K)
+  where
+    K: Eq,
+  {
+    add_expected(&mut self.expected, kind);
+    if let Some((idx, kinds)) = &mut self.recorded
+      && *idx == self.tok_idx
+    {
+      add_expected(kinds, kind);
+    }
+  }
+
+  /// Uses up some fuel, and returns whether there was any left.
+  fn use_fuel(&mut self) -> bool {
+    let Some(fuel) = self.fuel.checked_sub(1) else { return false };
+    self.fuel = fuel;
+    if fuel == 0 {
+      let text = self.peek_raw().map_or("<end of input>", |tok| tok.text);
+      always!(false, "parser is stuck at token {} ({text:?})", self.tok_idx);
+      return false;
+    }
+    true
+  }
+
   /// Records an error at the current token.
   pub fn error(&mut self, error: E) {
-    self.events.push(Some(Event::Error(error)));
+    self.push(Some(Event::Error(error, ErrorSpan::Next)));
+  }
+
+  /// Records an error for the `n` non-trivia tokens starting at the current token, or fewer if there
+  /// are not that many tokens left.
+  ///
+  /// The tokens are not consumed.
+  pub fn error_tokens(&mut self, error: E, n: usize) {
+    self.push(Some(Event::Error(error, ErrorSpan::Tokens(n))));
+  }
+
+  /// Records an error at the current token, then consumes it, wrapping it in a node of kind
+  /// `error_kind`. Does nothing else if there are no more tokens.
+  ///
+  /// This keeps the tree lossless when we have no idea what to do with a token.
+  pub fn err_and_bump(&mut self, error: E, error_kind: K) {
+    self.error(error);
+    if self.peek().is_some() {
+      let en = self.enter();
+      self.bump();
+      self.exit(en, error_kind);
+    }
   }
 
-  fn eat_trivia(&mut self, to_enter: &mut Vec<K>, sink: &mut dyn Sink<K, E>) {
-    while let Some(&tok) = self.tokens.get(self.tok_idx) {
-      if !tok.kind.is_trivia() {
+  /// Consumes tokens until the current token satisfies `stop` or there are no more tokens, wrapping
+  /// the consumed tokens, if any, in a node of kind `error_kind`.
+  fn skip_until<F>(&mut self, mut stop: F, error_kind: K)
+  where
+    F: FnMut(K) -> bool,
+  {
+    let en = self.enter();
+    let mut skipped = false;
+    while let Some(tok) = self.peek() {
+      if stop(tok.kind) {
         break;
       }
-      self.token(to_enter, sink, tok);
+      self.bump();
+      skipped = true;
+    }
+    if skipped {
+      self.exit(en, error_kind);
+    } else {
+      self.abandon(en);
     }
   }
 
-  fn token(&mut self, to_enter: &mut Vec<K>, sink: &mut dyn Sink<K, E>, tok: Token<'a, K>) {
-    for kind in to_enter.drain(..) {
-      sink.enter(kind);
-    }
-    sink.token(tok);
-    self.tok_idx += 1;
+  /// Writes the events so far into the `sink`, so that they, and the tokens they consumed, are no
+  /// longer kept in memory.
+  ///
+  /// Events are written only up to the first node which is still open, other than nodes from
+  /// [`Parser::enter_with`], a token partially consumed with [`Parser::bump_split`], or a [`Save`]
+  /// not yet used. Markers for the written events may no longer be used, as in e.g.
+  /// [`Parser::precede`] on an [`Exited`] from before the flush. Doing so panics.
+  ///
+  /// Call [`Parser::finish`] with the same `sink` when done.
+  pub fn flush(&mut self, sink: &mut dyn Sink<K, E>) {
+    self.flush_with(sink, &mut trivia::DefaultPolicy);
+  }
+
+  /// Like [`Parser::flush`], but with a policy for where trivia goes. Use the same policy for every
+  /// flush and [`Parser::finish_with`].
+  pub fn flush_with(&mut self, sink: &mut dyn Sink<K, E>, policy: &mut dyn TriviaPolicy<K>) {
+    self.saves.retain(|(alive, _, _)| alive.strong_count() != 0);
+    let saves = self.saves.iter().map(|&(_, events_len, _)| events_len);
+    let end = self.open.iter().copied().chain(self.split.map(|split| split.ev_idx)).chain(saves);
+    self.emit_until(end.min().unwrap_or_else(|| self.events_len()), sink, policy);
+    let saves = self.saves.iter().map(|&(_, _, tok_idx)| tok_idx);
+    let tok_idx = saves.chain([self.tok_idx, self.emitter.tok_idx()]).min();
+    self.tokens.drop_before(tok_idx.unwrap_or(self.tok_idx));
   }
 
   /// Finishes parsing, and writes the parsed tree into the `sink`.
   ///
+  /// Trivia goes where [`DefaultPolicy`](trivia::DefaultPolicy) puts it. Use
+  /// [`Parser::finish_with`] to change that.
+  ///
   /// # Panics
   ///
   /// On internal error.
-  pub fn finish(mut self, sink: &mut dyn Sink<K, E>) {
-    let mut to_enter = Vec::<K>::new();
-    let mut fst = None::<K>;
-    self.tok_idx = 0;
-    let mut kinds = Vec::new();
-    let mut levels: usize = 0;
+  pub fn finish(self, sink: &mut dyn Sink<K, E>) {
+    self.finish_with(sink, &mut trivia::DefaultPolicy);
+  }
+
+  /// Like [`Parser::finish`], but with a policy for where trivia goes.
+  ///
+  /// # Panics
+  ///
+  /// On internal error.
+  pub fn finish_with(mut self, sink: &mut dyn Sink<K, E>, policy: &mut dyn TriviaPolicy<K>) {
+    self.emit_until(self.events_len(), sink, policy);
+    self.emitter.finish(&mut self.tokens, sink, policy);
+  }
+
+  /// Finishes parsing like [`Parser::finish`], but returns the events instead of writing them into
+  /// a sink. They may be written into a sink later with [`event_log::replay`] or
+  /// [`event_log::into_replay`].
+  ///
+  /// # Panics
+  ///
+  /// If there was a [`Parser::flush`].
+  #[must_use]
+  pub fn into_events(mut self) -> Vec<event_log::Event<K, E>> {
+    assert_eq!(self.ev_base, 0, "into_events after flush");
+    let mut ret = Vec::new();
     for idx in 0..self.events.len() {
-      let Some(ev) = self.events[idx].take() else { continue };
-      match ev {
-        Event::Enter(kind, mut parent) => {
-          assert!(kinds.is_empty());
-          if fst.is_none() {
-            fst = Some(kind);
-          }
-          kinds.push(kind);
-          while let Some(p) = parent {
-            match self.events[p].take() {
-              Some(Event::Enter(kind, new_parent)) => {
-                kinds.push(kind);
-                parent = new_parent;
-              }
-              // abandoned precede
-              None => break,
-              ev => unreachable!("{:?} was {:?}, not Enter", parent, ev),
-            }
-          }
-          for kind in kinds.drain(..).rev() {
-            // keep as much trivia as possible outside of what we're entering.
-            if levels != 0 {
-              self.eat_trivia(&mut to_enter, sink);
+      self.take_resolved(idx, &mut ret);
+    }
+    ret
+  }
+
+  /// Writes the events up to the event at `end` into the `sink`, then forgets them.
+  fn emit_until(
+    &mut self,
+    end: usize,
+    sink: &mut dyn Sink<K, E>,
+    policy: &mut dyn TriviaPolicy<K>,
+  ) {
+    let mut resolved = Vec::new();
+    for idx in self.ev_base..end {
+      self.take_resolved(idx, &mut resolved);
+      for ev in resolved.drain(..) {
+        self.emitter.event(&mut self.tokens, sink, policy, ev);
+      }
+    }
+    self.events.drain(..end - self.ev_base);
+    self.ev_base = end;
+  }
+
+  /// Takes the event at `idx`, and pushes the events it resolves to onto `out`.
+  fn take_resolved(&mut self, idx: usize, out: &mut Vec<event_log::Event<K, E>>) {
+    let Some(ev) = self.event(idx).take() else { return };
+    let ev = match ev {
+      Event::Enter(kind, mut parent) => {
+        #[cfg(feature = "stats")]
+        self.stats.resolve(idx, kind);
+        let start = out.len();
+        out.push(event_log::Event::Enter(kind));
+        while let Some(p) = parent {
+          match self.event(p).take() {
+            Some(Event::Enter(kind, new_parent)) => {
+              #[cfg(feature = "stats")]
+              self.stats.resolve(p, kind);
+              out.push(event_log::Event::Enter(kind));
+              parent = new_parent;
             }
-            to_enter.push(kind);
-            levels += 1;
-          }
-        }
-        Event::Exit => {
-          if to_enter.pop().is_none() {
-            sink.exit();
-          }
-          levels -= 1;
-          // keep as much trivia as possible outside of top-level items.
-          if levels == 1 {
-            self.eat_trivia(&mut to_enter, sink);
+            // abandoned precede
+            None => break,
+            ev => unreachable!("{:?} was {:?}, not Enter", parent, ev),
           }
         }
-        Event::Token => {
-          self.eat_trivia(&mut to_enter, sink);
-          self.token(&mut to_enter, sink, self.tokens[self.tok_idx]);
-        }
-        Event::Error(expected) => sink.error(expected),
+        // the outermost node is entered first.
+        out[start..].reverse();
+        return;
       }
-    }
-    assert_eq!(levels, 0);
-    // give an empty node for the empty parse
-    if self.tok_idx == 0
-      && let Some(fst) = fst
-    {
-      sink.enter(fst);
-      sink.exit();
-    }
+      Event::Token(remap) => event_log::Event::Token(remap),
+      Event::Split(kind, _, len) => event_log::Event::Split(kind, len),
+      Event::Exit => event_log::Event::Exit,
+      Event::Error(error, ErrorSpan::Next) => event_log::Event::Error(error),
+      Event::Error(error, ErrorSpan::Tokens(n)) => event_log::Event::ErrorTokens(error, n),
+      Event::Error(error, ErrorSpan::Node) => event_log::Event::ErrorNode(error),
+    };
+    out.push(ev);
   }
 }
 
@@ -313,8 +813,30 @@ where
   }
 
   /// Returns whether the token `n` ahead has the given `kind`.
+  ///
+  /// If `n == 0`, `kind` is added to the [expected kinds](Parser::expected).
   pub fn at_n(&mut self, n: usize, kind: K) -> bool {
-    self.peek_n(n).is_some_and(|tok| tok.kind == kind)
+    let ret = self.peek_n(n).is_some_and(|tok| tok.kind == kind);
+    if n == 0 {
+      self.add_expected(kind);
+    }
+    ret
+  }
+
+  /// Records an error at the current token that one of the [expected kinds](Parser::expected) was
+  /// expected, then forgets those kinds.
+  ///
+  /// # Panics
+  ///
+  /// If there were no expected kinds.
+  pub fn error_expected(&mut self) {
+    let error = match self.expected.as_slice() {
+      [] => panic!("error_expected with no expected kinds"),
+      &[kind] => E::expected(kind),
+      kinds => E::expected_one_of(kinds),
+    };
+    self.expected.clear();
+    self.error(error);
   }
 
   /// If the current token's kind is `kind`, then this consumes it, else this
@@ -323,10 +845,27 @@ where
     if self.at(kind) {
       Some(self.bump())
     } else {
-      self.error(E::expected(kind));
+      self.error_expected();
       None
     }
   }
+
+  /// Like [`Parser::eat`], but recovers when the current token's kind is not `kind`.
+  ///
+  /// After recording the error, this skips tokens until the current token has kind `kind` or a kind
+  /// in the `recovery` set, wrapping the skipped tokens in a node of kind `error_kind`. Tokens with
+  /// kinds in the recovery set are left for an outer rule to consume, but if we stopped at a token
+  /// of kind `kind`, it is consumed.
+  ///
+  /// Returns the token of kind `kind` if it was eaten.
+  pub fn eat_or_recover(&mut self, kind: K, recovery: &[K], error_kind: K) -> Option<Token<'a, K>> {
+    if self.at(kind) {
+      return Some(self.bump());
+    }
+    self.error_expected();
+    self.skip_until(|k| k == kind || recovery.contains(&k), error_kind);
+    self.at(kind).then(|| self.bump())
+  }
 }
 
 /// A marker for a syntax construct that is mid-parse. If this is not consumed
@@ -336,6 +875,11 @@ pub struct Entered {
   bomb: DropBomb,
   ev_idx: usize,
   tok_idx: usize,
+  /// The index of the first event in the chain of nodes made with [`Parser::precede`] this will be
+  /// part of.
+  chain: usize,
+  /// Whether this is from [`Parser::enter_with`].
+  known: bool,
 }
 
 /// A marker for a syntax construct that has been fully parsed.
@@ -357,6 +901,7 @@ pub struct Entered {
 #[derive(Debug, Clone, Copy)]
 pub struct Exited {
   ev_idx: usize,
+  chain: usize,
   is_empty: bool,
 }
 
@@ -372,14 +917,41 @@ impl Exited {
 #[derive(Debug)]
 #[must_use]
 pub struct Save {
+  /// Only the parser has weak references to this, so it knows when the save is dropped.
+  alive: Arc<()>,
   tok_idx: usize,
   events_len: usize,
+  split: Option<Split>,
 }
 
 /// An error that can be generated from an expected syntax kind.
 pub trait Expected<K> {
   /// Generate the error.
   fn expected(kind: K) -> Self;
+
+  /// Generate the error when any of the `kinds` would have been fine, as in "expected one of `;`,
+  /// `)`, identifier". `kinds` has more than one kind, and is ordered with the most recently tested
+  /// kind last.
+  ///
+  /// By default, this reports only the last kind.
+  fn expected_one_of(kinds: &[K]) -> Self
+  where
+    Self: Sized,
+    K: Copy,
+  {
+    Self::expected(kinds[kinds.len() - 1])
+  }
+}
+
+/// Adds `kind` to the end of `kinds`, removing it from earlier in `kinds` if it was there.
+fn add_expected<K>(kinds: &mut Vec<K>, kind: K)
+where
+  K: Eq,
+{
+  if let Some(idx) = kinds.iter().position(|k| *k == kind) {
+    kinds.remove(idx);
+  }
+  kinds.push(kind);
 }
 
 /// Types which can construct a syntax tree.
@@ -392,22 +964,51 @@ pub trait Sink<K, E> {
   fn exit(&mut self);
   /// Reports an error.
   fn error(&mut self, error: E);
+  /// Reports an error for the given byte range of the input.
+  ///
+  /// By default, this ignores the range and calls [`Sink::error`].
+  fn error_range(&mut self, error: E, range: Range<usize>) {
+    let _ = range;
+    self.error(error);
+  }
+}
+
+/// The state of a token that was partially consumed by [`Parser::bump_split`].
+#[derive(Debug, Clone, Copy)]
+struct Split {
+  tok_idx: usize,
+  offset: usize,
+  /// The index of the latest [`Event::Split`] for the token, which has the kind for the rest of it.
+  ev_idx: usize,
 }
 
 enum Event<K, E> {
   Enter(K, Option<usize>),
-  Token,
+  Token(Option<K>),
+  Split(K, K, usize),
   Exit,
-  Error(E),
+  Error(E, ErrorSpan),
+}
+
+/// What an [`Event::Error`] is for.
+#[derive(Debug, Clone, Copy)]
+enum ErrorSpan {
+  /// The next non-trivia token.
+  Next,
+  /// Some number of non-trivia tokens, starting at the next one.
+  Tokens(usize),
+  /// The node being exited.
+  Node,
 }
 
 impl<K, E> fmt::Debug for Event<K, E> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
       Event::Enter(_, n) => f.debug_tuple("Enter").field(n).finish(),
-      Event::Token => f.debug_tuple("Token").finish(),
+      Event::Token(_) => f.debug_tuple("Token").finish(),
+      Event::Split(_, _, n) => f.debug_tuple("Split").field(n).finish(),
       Event::Exit => f.debug_tuple("Exit").finish(),
-      Event::Error(_) => f.debug_tuple("Error").finish(),
+      Event::Error(_, span) => f.debug_tuple("Error").field(span).finish(),
     }
   }
 }
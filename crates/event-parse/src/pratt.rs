@@ -0,0 +1,120 @@
+//! Parsing expressions with prefix, infix, and postfix operators of differing precedence, with
+//! Pratt parsing (aka precedence climbing).
+//!
+//! Each operator has a binding power on each side it has an operand. Higher binding powers bind
+//! tighter. For infix operators, a left binding power less than the right one makes the operator
+//! left-associative, and vice versa.
+
+use crate::{Exited, Parser};
+use token::Triviable;
+
+/// An operator between two operands, like `+` in `a + b`.
+#[derive(Debug, Clone, Copy)]
+pub struct Infix<K> {
+  /// The kind of the operator token.
+  pub op: K,
+  /// The binding power on the left.
+  pub left: u8,
+  /// The binding power on the right.
+  pub right: u8,
+  /// The kind of the node for the whole operation.
+  pub node: K,
+}
+
+/// An operator before its operand, like `-` in `-a`.
+#[derive(Debug, Clone, Copy)]
+pub struct Prefix<K> {
+  /// The kind of the operator token.
+  pub op: K,
+  /// The binding power on the right.
+  pub right: u8,
+  /// The kind of the node for the whole operation.
+  pub node: K,
+}
+
+/// An operator after its operand, like `?` in `a?`.
+#[derive(Debug, Clone, Copy)]
+pub struct Postfix<K> {
+  /// The kind of the operator token.
+  pub op: K,
+  /// The binding power on the left.
+  pub left: u8,
+  /// The kind of the node for the whole operation.
+  pub node: K,
+}
+
+/// A table of operators.
+#[derive(Debug, Clone, Copy)]
+pub struct Ops<'o, K> {
+  /// The infix operators.
+  pub infix: &'o [Infix<K>],
+  /// The prefix operators.
+  pub prefix: &'o [Prefix<K>],
+  /// The postfix operators.
+  pub postfix: &'o [Postfix<K>],
+}
+
+impl<K, E> Parser<'_, K, E>
+where
+  K: Copy + Triviable + Eq,
+{
+  /// Parses an expression made of operators from `ops` and the atoms parsed by `atom`, like
+  /// literals or parenthesized expressions. Only operators with binding power at least `min` will
+  /// be parsed, so use `0` to parse a whole expression.
+  ///
+  /// Returns the completed expression, or `None` if there was no expression, i.e. `atom` returned
+  /// `None` at the start.
+  ///
+  /// `atom` should report an error itself if it returns `None`, since that also happens when an
+  /// operator is missing an operand. The node for such an operator is still completed.
+  ///
+  /// Like with [`Parser::at`], each operator is added to the [expected kinds](Parser::expected)
+  /// wherever it could be next.
+  ///
+  /// All of the nodes are created with [`Parser::precede`] on nodes created during this call, so
+  /// it's fine to call this between a [`Parser::save`] and [`Parser::ok_since`].
+  pub fn pratt<F>(&mut self, ops: &Ops<'_, K>, min: u8, atom: &mut F) -> Option<Exited>
+  where
+    F: FnMut(&mut Self) -> Option<Exited>,
+  {
+    for op in ops.prefix {
+      self.add_expected(op.op);
+    }
+    let mut lhs = match self.peek().and_then(|tok| ops.prefix.iter().find(|op| op.op == tok.kind)) {
+      Some(op) => {
+        let en = self.enter();
+        self.bump();
+        self.pratt(ops, op.right, atom);
+        self.exit(en, op.node)
+      }
+      None => atom(self)?,
+    };
+    loop {
+      // the operators are expected even if we stop before them here, since they may continue an
+      // enclosing expression.
+      for op in ops.postfix.iter().map(|op| op.op).chain(ops.infix.iter().map(|op| op.op)) {
+        self.add_expected(op);
+      }
+      let Some(tok) = self.peek() else { break };
+      if let Some(op) = ops.postfix.iter().find(|op| op.op == tok.kind) {
+        if op.left < min {
+          break;
+        }
+        let en = self.precede(lhs);
+        self.bump();
+        lhs = self.exit(en, op.node);
+      } else if let Some(op) = ops.infix.iter().find(|op| op.op == tok.kind) {
+        if op.left < min {
+          break;
+        }
+        let en = self.precede(lhs);
+        self.bump();
+        self.pratt(ops, op.right, atom);
+        lhs = self.exit(en, op.node);
+      } else {
+        break;
+      }
+    }
+    Some(lhs)
+  }
+}
@@ -0,0 +1,186 @@
+//! Incremental reparsing of Rowan trees.
+//!
+//! Instead of reparsing a whole file after every edit, we find the smallest node around the edit
+//! that the grammar knows how to parse on its own, parse only the new text for that node, and
+//! splice the result into the old tree.
+
+use crate::Parser;
+use crate::rowan_sink::{Error, RowanSinkWithCache};
+use crate::trivia::{DefaultPolicy, TriviaPolicy};
+use rowan::{Language, NodeCache, NodeOrToken, SyntaxKind, SyntaxNode, TextRange, TextSize};
+use token::{Token, Triviable};
+
+/// Parses the contents of a node of some kind, i.e. everything between entering and exiting it.
+pub type Reparser<K, E> = for<'a> fn(&mut Parser<'a, K, E>);
+
+/// The result of a successful reparse.
+#[derive(Debug)]
+pub struct Reparsed<L, E>
+where
+  L: Language,
+{
+  /// The new root, with the reparsed node spliced in.
+  pub root: SyntaxNode<L>,
+  /// The range of the reparsed node in the old text.
+  pub old_range: TextRange,
+  /// The range of the reparsed node in the new text.
+  pub new_range: TextRange,
+  /// The errors from reparsing the node, with ranges in the new text.
+  pub errors: Vec<Error<L::Kind, E>>,
+}
+
+impl<L, E> Reparsed<L, E>
+where
+  L: Language,
+{
+  /// Combines the errors from the old parse with the errors from this reparse.
+  ///
+  /// Old errors inside the old range of the reparsed node are assumed to have come from that node,
+  /// so they are dropped. Other old errors, like those for a node containing the reparsed node, are
+  /// kept, and moved to account for the edit. The errors are sorted by the start of their range.
+  #[must_use]
+  pub fn splice_errors(self, old: Vec<Error<L::Kind, E>>) -> Vec<Error<L::Kind, E>> {
+    let (old_range, new_range) = (self.old_range, self.new_range);
+    let shift = |pos: TextSize| {
+      if pos <= old_range.start() {
+        pos
+      } else if pos >= old_range.end() {
+        pos - old_range.end() + new_range.end()
+      } else {
+        pos.min(new_range.end())
+      }
+    };
+    let mut ret = self.errors;
+    for error in old {
+      let inside = old_range.contains_range(error.range)
+        && error.range.end() > old_range.start()
+        && error.range.start() < old_range.end();
+      if !inside {
+        let range = TextRange::new(shift(error.range.start()), shift(error.range.end()));
+        ret.push(Error { range, ..error });
+      }
+    }
+    // stable, so errors from the reparse come before old errors at the same place, as errors for
+    // enclosing nodes do in a full parse.
+    ret.sort_by_key(|e| e.range.start());
+    ret
+  }
+}
+
+/// Tries to reparse only part of the tree at `root` after the `change` was applied to its text.
+///
+/// `pos_db` is for the old text, i.e. the text of `root`, and is used to find where the `change`
+/// is. `tokens` are all of the tokens for the new text. `reparser` returns, for a node kind, the
+/// function to parse the contents of such a node, or `None` if such nodes can't be reparsed on
+/// their own.
+///
+/// The reparsed node is the smallest one strictly containing the edit whose kind has a reparser.
+/// Returns `None` if there was no such node, or if the new text for it didn't parse into exactly
+/// one such node. In that case, the caller should fall back to a full parse.
+pub fn get<L, E, F>(
+  root: &SyntaxNode<L>,
+  pos_db: &text_pos::PositionDb,
+  change: &apply_changes::Change,
+  tokens: &[Token<'_, L::Kind>],
+  reparser: F,
+) -> Option<Reparsed<L, E>>
+where
+  L: Language,
+  L::Kind: Copy + Triviable + Into<SyntaxKind>,
+  F: Fn(L::Kind) -> Option<Reparser<L::Kind, E>>,
+{
+  get_with_cache(root, pos_db, change, tokens, reparser, &mut NodeCache::default())
+}
+
+/// Like [`get`], but interns the reparsed nodes and tokens in the `cache`, as with
+/// [`RowanSinkWithCache::with_cache`].
+pub fn get_with_cache<L, E, F>(
+  root: &SyntaxNode<L>,
+  pos_db: &text_pos::PositionDb,
+  change: &apply_changes::Change,
+  tokens: &[Token<'_, L::Kind>],
+  reparser: F,
+  cache: &mut NodeCache,
+) -> Option<Reparsed<L, E>>
+where
+  L: Language,
+  L::Kind: Copy + Triviable + Into<SyntaxKind>,
+  F: Fn(L::Kind) -> Option<Reparser<L::Kind, E>>,
+{
+  get_with(root, pos_db, change, tokens, reparser, cache, &mut DefaultPolicy)
+}
+
+/// Like [`get_with_cache`], but with a policy for where trivia goes, as with
+/// [`Parser::finish_with`]. Use the same policy as for the parse that made the `root`.
+pub fn get_with<L, E, F>(
+  root: &SyntaxNode<L>,
+  pos_db: &text_pos::PositionDb,
+  change: &apply_changes::Change,
+  tokens: &[Token<'_, L::Kind>],
+  reparser: F,
+  cache: &mut NodeCache,
+  policy: &mut dyn TriviaPolicy<L::Kind>,
+) -> Option<Reparsed<L, E>>
+where
+  L: Language,
+  L::Kind: Copy + Triviable + Into<SyntaxKind>,
+  F: Fn(L::Kind) -> Option<Reparser<L::Kind, E>>,
+{
+  let edit = pos_db.text_range_utf16(change.range?)?;
+  let start = match root.covering_element(edit) {
+    NodeOrToken::Node(node) => node,
+    NodeOrToken::Token(token) => token.parent()?,
+  };
+  let (node, f) = start.ancestors().find_map(|node| {
+    let range = node.text_range();
+    let contains = range.start() < edit.start() && edit.end() < range.end();
+    contains.then(|| reparser(node.kind())).flatten().map(|f| (node, f))
+  })?;
+  let old_range = node.text_range();
+  let new_len = old_range.len() - edit.len() + TextSize::of(change.text.as_str());
+  let new_range = TextRange::at(old_range.start(), new_len);
+  let tokens = tokens_in(tokens, new_range)?;
+  let mut p = Parser::new(tokens);
+  let en = p.enter();
+  f(&mut p);
+  let rest = p.peek().is_some();
+  p.exit(en, node.kind());
+  if rest {
+    return None;
+  }
+  let mut sink = RowanSinkWithCache::with_cache(cache);
+  p.finish_with(&mut sink, policy);
+  let (new_node, errors) = sink.finish::<L>();
+  if new_node.text_range().len() != new_len {
+    return None;
+  }
+  let root = SyntaxNode::new_root(node.replace_with(new_node.green().into_owned()));
+  let errors = errors
+    .into_iter()
+    .map(|error| Error { range: error.range + new_range.start(), ..error })
+    .collect();
+  Some(Reparsed { root, old_range, new_range, errors })
+}
+
+/// Returns the tokens exactly covering the range, or `None` if the range doesn't start and end on
+/// token boundaries.
+fn tokens_in<'t, 'a, K>(
+  tokens: &'t [Token<'a, K>],
+  range: TextRange,
+) -> Option<&'t [Token<'a, K>]> {
+  let mut offset = TextSize::from(0);
+  let mut start = None::<usize>;
+  for (idx, token) in tokens.iter().enumerate() {
+    if offset == range.start() {
+      start = Some(idx);
+    }
+    if offset == range.end() {
+      return Some(&tokens[start?..idx]);
+    }
+    if offset > range.end() {
+      return None;
+    }
+    offset += TextSize::of(token.text);
+  }
+  (offset == range.end()).then(|| start.map(|start| &tokens[start..])).flatten()
+}
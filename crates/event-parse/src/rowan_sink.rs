@@ -1,29 +1,58 @@
 //! A [`Sink`] for Rowan trees.
 
 use crate::Sink;
-use rowan::{GreenNodeBuilder, Language, SyntaxKind, SyntaxNode, TextRange, TextSize};
+use always::convert::usize_to_u32;
+use diagnostic::{Code, Diagnostic, Severity};
+use rowan::{GreenNodeBuilder, Language, NodeCache, SyntaxKind, SyntaxNode, TextRange, TextSize};
+use std::ops::Range;
+use text_pos::{PositionDb, RangeUtf16};
 use token::{Token, Triviable};
 
 /// The sink, which wraps a Rowan `GreenNodeBuilder`.
+///
+/// Each sink interns the nodes and tokens it makes in its own cache. Use
+/// [`RowanSinkWithCache::with_cache`] to share them across sinks instead.
+pub type RowanSink<K, E> = RowanSinkWithCache<'static, K, E>;
+
+/// A [`RowanSink`] which may intern the nodes and tokens it makes in a cache shared with other
+/// sinks, e.g. for many files or many reparses of one file.
 #[derive(Debug)]
-pub struct RowanSink<K, E> {
-  builder: GreenNodeBuilder<'static>,
+pub struct RowanSinkWithCache<'c, K, E> {
+  builder: GreenNodeBuilder<'c>,
   cur: (TextRange, Option<K>),
   errors: Vec<Error<K, E>>,
   no_range: Vec<E>,
 }
 
-impl<K, E> RowanSink<K, E>
+impl<'c, K, E> RowanSinkWithCache<'c, K, E> {
+  /// Returns a new sink which interns nodes and tokens in the `cache`, so that identical ones are
+  /// shared with those from other sinks using the same cache.
+  pub fn with_cache(cache: &'c mut NodeCache) -> Self {
+    Self {
+      builder: GreenNodeBuilder::with_cache(cache),
+      cur: (TextRange::empty(0.into()), None),
+      errors: Vec::new(),
+      no_range: Vec::new(),
+    }
+  }
+}
+
+impl<K, E> RowanSinkWithCache<'_, K, E>
 where
   K: Clone,
 {
   /// Finish the builder.
+  ///
+  /// The errors are sorted by the start of their range, so they are in source order.
   #[must_use]
   pub fn finish<L>(mut self) -> (SyntaxNode<L>, Vec<Error<K, E>>)
   where
     L: Language,
   {
     self.extend_errors();
+    // errors for nodes are only known on exit, after errors inside them. stable, so errors at the
+    // same place stay in the order they were reported.
+    self.errors.sort_by_key(|e| e.range.start());
     let root = SyntaxNode::new_root(self.builder.finish());
     (root, self.errors)
   }
@@ -38,7 +67,7 @@ where
   }
 }
 
-impl<K, E> Default for RowanSink<K, E> {
+impl<K, E> Default for RowanSinkWithCache<'_, K, E> {
   fn default() -> Self {
     Self {
       builder: GreenNodeBuilder::default(),
@@ -49,7 +78,7 @@ impl<K, E> Default for RowanSink<K, E> {
   }
 }
 
-impl<K, E> Sink<K, E> for RowanSink<K, E>
+impl<K, E> Sink<K, E> for RowanSinkWithCache<'_, K, E>
 where
   K: Into<SyntaxKind> + Triviable + Clone,
 {
@@ -76,6 +105,12 @@ where
   fn error(&mut self, error: E) {
     self.no_range.push(error);
   }
+
+  fn error_range(&mut self, error: E, range: Range<usize>) {
+    let start = TextSize::from(usize_to_u32(range.start));
+    let end = TextSize::from(usize_to_u32(range.end));
+    self.errors.push(Error { range: TextRange::new(start, end), kind: None, inner: error });
+  }
 }
 
 /// An error.
@@ -83,8 +118,49 @@ where
 pub struct Error<K, E> {
   /// The range.
   pub range: TextRange,
-  /// The syntax kind.
+  /// The syntax kind of the token at the error, if the error was for one token.
   pub kind: Option<K>,
   /// The inner error.
   pub inner: E,
 }
+
+impl<K, E> Error<K, E>
+where
+  E: ToDiagnostic<K>,
+{
+  /// Returns the diagnostic for this error. If the range is out of bounds for the text of the
+  /// `pos_db`, the diagnostic is at the end of the text instead.
+  #[must_use]
+  pub fn to_diagnostic(&self, pos_db: &PositionDb) -> Diagnostic {
+    let range = pos_db
+      .range_utf16(self.range)
+      .unwrap_or_else(|| RangeUtf16::zero(pos_db.end_position_utf16()));
+    let message = self.inner.message(self.kind.as_ref());
+    Diagnostic::new(range, message, self.inner.severity(), self.inner.code())
+  }
+}
+
+/// Returns the diagnostics for the `errors`, as from [`RowanSinkWithCache::finish`], as with
+/// [`Error::to_diagnostic`].
+#[must_use]
+pub fn diagnostics<K, E>(pos_db: &PositionDb, errors: &[Error<K, E>]) -> Vec<Diagnostic>
+where
+  E: ToDiagnostic<K>,
+{
+  errors.iter().map(|error| error.to_diagnostic(pos_db)).collect()
+}
+
+/// An error which can be shown as a [`Diagnostic`].
+pub trait ToDiagnostic<K> {
+  /// Returns the message, given the syntax kind of the token at the error, if the error was for
+  /// one token.
+  fn message(&self, found: Option<&K>) -> String;
+
+  /// Returns the severity. Defaults to [`Severity::Error`].
+  fn severity(&self) -> Severity {
+    Severity::Error
+  }
+
+  /// Returns the code.
+  fn code(&self) -> Code;
+}
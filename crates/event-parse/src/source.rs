@@ -0,0 +1,66 @@
+//! Where the parser gets its tokens from.
+
+use std::collections::VecDeque;
+use std::fmt;
+use token::Token;
+
+/// The tokens for a parser, either all up front or pulled lazily from an iterator.
+pub(crate) enum Source<'a, K> {
+  Slice(&'a [Token<'a, K>]),
+  Iter {
+    /// The tokens pulled from `iter` but not yet dropped.
+    buf: VecDeque<Token<'a, K>>,
+    /// The index of the first token in `buf`.
+    base: usize,
+    /// Fused, since we may call `next` again after it returned `None`.
+    iter: Box<dyn Iterator<Item = Token<'a, K>> + Send + 'a>,
+  },
+}
+
+impl<'a, K> Source<'a, K>
+where
+  K: Copy,
+{
+  /// Returns the token at `idx`, pulling more tokens if needed.
+  ///
+  /// # Panics
+  ///
+  /// If the token was dropped.
+  pub(crate) fn get(&mut self, idx: usize) -> Option<Token<'a, K>> {
+    match self {
+      Source::Slice(tokens) => tokens.get(idx).copied(),
+      Source::Iter { buf, base, iter } => {
+        let idx = idx.checked_sub(*base).expect("token was already dropped by a flush");
+        while buf.len() <= idx {
+          buf.push_back(iter.next()?);
+        }
+        Some(buf[idx])
+      }
+    }
+  }
+}
+
+impl<K> Source<'_, K> {
+  /// Drops the tokens before `idx`, if they are not in a slice.
+  pub(crate) fn drop_before(&mut self, idx: usize) {
+    if let Source::Iter { buf, base, .. } = self {
+      let n = idx.saturating_sub(*base).min(buf.len());
+      buf.drain(..n);
+      *base += n;
+    }
+  }
+}
+
+impl<K> fmt::Debug for Source<'_, K>
+where
+  K: fmt::Debug,
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Source::Slice(tokens) => f.debug_tuple("Slice").field(tokens).finish(),
+      Source::Iter { buf, base, .. } => {
+        f.debug_struct("Iter").field("buf", buf).field("base", base).finish_non_exhaustive()
+      }
+    }
+  }
+}
@@ -0,0 +1,143 @@
+//! Statistics about a parse, for finding which parts of a grammar are slow or make many events.
+//!
+//! Get them with [`Parser::stats`](crate::Parser::stats). They implement [`fmt::Display`], so they
+//! can be logged together with e.g. how long the parse took:
+//!
+//! ```ignore
+//! let ((), elapsed) = elapsed::time(|| root(&mut p));
+//! log::info!("parsed in {elapsed:?}\n{}", p.stats());
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// Statistics about a parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats<K> {
+  /// The number of events recorded, including those later thrown away by backtracking.
+  pub events: usize,
+  /// The number of times the parser restored to a save, as with a failed
+  /// [`Parser::ok_since`](crate::Parser::ok_since).
+  pub backtracks: usize,
+  /// The stats for each kind of node exited, with the most common kinds first.
+  pub nodes: Vec<NodeStats<K>>,
+}
+
+/// Statistics about the nodes of one kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStats<K> {
+  /// The kind.
+  pub kind: K,
+  /// The number of nodes of the kind exited, including those later thrown away by backtracking.
+  pub count: usize,
+  /// The max `n` given to [`Parser::peek_n`](crate::Parser::peek_n) while parsing a node of the
+  /// kind, not counting while parsing a node in it.
+  pub max_peek: usize,
+}
+
+impl<K> fmt::Display for Stats<K>
+where
+  K: fmt::Display,
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "events: {}", self.events)?;
+    writeln!(f, "backtracks: {}", self.backtracks)?;
+    writeln!(f, "nodes:")?;
+    for node in &self.nodes {
+      writeln!(f, "  {}: {} (max peek {})", node.kind, node.count, node.max_peek)?;
+    }
+    Ok(())
+  }
+}
+
+/// Records stats as the parser runs.
+///
+/// This needs nothing of the kinds, so that turning on the `stats` feature does not add bounds to
+/// the parser. The kinds of the nodes are only counted up in [`Recorder::get`].
+#[derive(Debug)]
+pub(crate) struct Recorder<K> {
+  events: usize,
+  backtracks: usize,
+  /// For each node exited, in order, the index of the event with its kind, its kind if that event
+  /// is gone, and the max peek while parsing it.
+  exits: Vec<(usize, Option<K>, usize)>,
+  /// The index in `exits` of each exit whose kind is still in the event at that index.
+  pending: HashMap<usize, usize>,
+  /// For each node being parsed, the index of the event with its kind, its kind if that event is
+  /// gone, and the max peek so far.
+  open: Vec<(usize, Option<K>, usize)>,
+}
+
+impl<K> Default for Recorder<K> {
+  fn default() -> Self {
+    Self { events: 0, backtracks: 0, exits: Vec::new(), pending: HashMap::new(), open: Vec::new() }
+  }
+}
+
+impl<K> Recorder<K> {
+  pub(crate) fn event(&mut self) {
+    self.events += 1;
+  }
+
+  pub(crate) fn backtrack(&mut self) {
+    self.backtracks += 1;
+  }
+
+  pub(crate) fn enter(&mut self, ev_idx: usize) {
+    self.open.push((ev_idx, None, 0));
+  }
+
+  pub(crate) fn peek(&mut self, n: usize) {
+    if let Some((_, _, max)) = self.open.last_mut() {
+      *max = (*max).max(n);
+    }
+  }
+
+  /// The events of an abandoned node belong to its parent, so its peeks do too.
+  pub(crate) fn abandon(&mut self) {
+    if let Some((_, _, max)) = self.open.pop() {
+      self.peek(max);
+    }
+  }
+
+  pub(crate) fn exit(&mut self) {
+    let Some((ev_idx, kind, max_peek)) = self.open.pop() else { return };
+    if kind.is_none() {
+      self.pending.insert(ev_idx, self.exits.len());
+    }
+    self.exits.push((ev_idx, kind, max_peek));
+  }
+
+  /// Keeps the kind in the event at `ev_idx`, since the event is going away.
+  pub(crate) fn resolve(&mut self, ev_idx: usize, kind: K) {
+    if let Some(idx) = self.pending.remove(&ev_idx) {
+      self.exits[idx].1 = Some(kind);
+    } else if let Some(open) = self.open.iter_mut().rev().find(|(idx, _, _)| *idx == ev_idx) {
+      open.1 = Some(kind);
+    }
+  }
+
+  /// Returns the stats, getting the kinds still in events with `kind_at`.
+  pub(crate) fn get<F>(&self, kind_at: F) -> Stats<K>
+  where
+    K: Copy + Eq + Hash,
+    F: Fn(usize) -> K,
+  {
+    let mut nodes = Vec::<NodeStats<K>>::new();
+    let mut idx = HashMap::<K, usize>::new();
+    for &(ev_idx, kind, max_peek) in &self.exits {
+      let kind = kind.unwrap_or_else(|| kind_at(ev_idx));
+      let &mut idx = idx.entry(kind).or_insert_with(|| {
+        nodes.push(NodeStats { kind, count: 0, max_peek: 0 });
+        nodes.len() - 1
+      });
+      let node = &mut nodes[idx];
+      node.count += 1;
+      node.max_peek = node.max_peek.max(max_peek);
+    }
+    // stable, so kinds with the same count stay in the order they were first exited.
+    nodes.sort_by_key(|node| std::cmp::Reverse(node.count));
+    Stats { events: self.events, backtracks: self.backtracks, nodes }
+  }
+}
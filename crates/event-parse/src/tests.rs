@@ -0,0 +1,1400 @@
+//! Tests, using a small language of s-expressions like `(a (b c) d)`.
+
+use crate::event_log::{self, Encode};
+use crate::trivia::{Attach, ClassPolicy, TriviaPolicy};
+use crate::{Exited, Expected, Parser, Sink, debug_sink, pratt, source::Source};
+use std::fmt::{self, Write as _};
+use token::{Token, TriviaClass, Triviable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u16)]
+enum K {
+  Ws,
+  Comment,
+  DocComment,
+  LRound,
+  RRound,
+  Name,
+  Plus,
+  Minus,
+  Star,
+  Caret,
+  Question,
+  Root,
+  List,
+  Error,
+  Atom,
+  Add,
+  Mul,
+  Pow,
+  Neg,
+  Try,
+}
+
+impl K {
+  const ALL: [K; 20] = [
+    K::Ws,
+    K::Comment,
+    K::DocComment,
+    K::LRound,
+    K::RRound,
+    K::Name,
+    K::Plus,
+    K::Minus,
+    K::Star,
+    K::Caret,
+    K::Question,
+    K::Root,
+    K::List,
+    K::Error,
+    K::Atom,
+    K::Add,
+    K::Mul,
+    K::Pow,
+    K::Neg,
+    K::Try,
+  ];
+}
+
+impl Triviable for K {
+  fn is_trivia(&self) -> bool {
+    matches!(*self, K::Ws | K::Comment | K::DocComment)
+  }
+
+  fn trivia_class(&self) -> Option<TriviaClass> {
+    match *self {
+      K::Ws => Some(TriviaClass::Whitespace),
+      K::Comment => Some(TriviaClass::Comment),
+      K::DocComment => Some(TriviaClass::DocComment),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for K {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Debug::fmt(self, f)
+  }
+}
+
+/// The kinds that were expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct E(Vec<K>);
+
+impl Expected<K> for E {
+  fn expected(kind: K) -> Self {
+    E(vec![kind])
+  }
+
+  fn expected_one_of(kinds: &[K]) -> Self {
+    E(kinds.to_vec())
+  }
+}
+
+impl fmt::Display for E {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("expected")?;
+    for kind in &self.0 {
+      write!(f, " {kind}")?;
+    }
+    Ok(())
+  }
+}
+
+fn lex(s: &str) -> Vec<Token<'_, K>> {
+  let bs = s.as_bytes();
+  let mut ret = Vec::new();
+  let mut idx = 0;
+  while idx < bs.len() {
+    let start = idx;
+    let kind = match bs[idx] {
+      b'(' => K::LRound,
+      b')' => K::RRound,
+      b'+' => K::Plus,
+      b'-' => K::Minus,
+      b'*' => K::Star,
+      b'^' => K::Caret,
+      b'?' => K::Question,
+      b';' => {
+        let doc = bs.get(idx + 1) == Some(&b';');
+        while bs.get(idx + 1).is_some_and(|&b| b != b'\n') {
+          idx += 1;
+        }
+        if doc { K::DocComment } else { K::Comment }
+      }
+      b' ' | b'\n' => {
+        while bs.get(idx + 1).is_some_and(|&b| b == b' ' || b == b'\n') {
+          idx += 1;
+        }
+        K::Ws
+      }
+      _ => {
+        while bs.get(idx + 1).is_some_and(u8::is_ascii_alphanumeric) {
+          idx += 1;
+        }
+        K::Name
+      }
+    };
+    idx += 1;
+    ret.push(Token { kind, text: &s[start..idx] });
+  }
+  ret
+}
+
+fn root(p: &mut Parser<'_, K, E>) {
+  let en = p.enter();
+  while p.peek().is_some() {
+    sexp(p);
+  }
+  p.exit(en, K::Root);
+}
+
+fn sexp(p: &mut Parser<'_, K, E>) {
+  if p.at(K::LRound) {
+    let en = p.enter();
+    p.bump();
+    list_contents(p);
+    p.exit(en, K::List);
+  } else {
+    p.eat(K::Name);
+  }
+}
+
+fn list_contents(p: &mut Parser<'_, K, E>) {
+  while p.peek().is_some_and(|tok| tok.kind != K::RRound) {
+    sexp(p);
+  }
+  p.eat(K::RRound);
+}
+
+/// Renders a tree as an s-expression with the kinds of the nodes, and the errors after it.
+#[derive(Debug, Default)]
+struct Render {
+  out: String,
+  errors: Vec<E>,
+}
+
+impl Sink<K, E> for Render {
+  fn enter(&mut self, kind: K) {
+    write!(self.out, "{kind}(").unwrap();
+  }
+
+  fn token(&mut self, token: Token<'_, K>) {
+    write!(self.out, "{:?}", token.text).unwrap();
+  }
+
+  fn exit(&mut self) {
+    self.out.push(')');
+  }
+
+  fn error(&mut self, error: E) {
+    self.errors.push(error);
+  }
+}
+
+fn check<F>(s: &str, f: F, want: &str, want_errors: &[E])
+where
+  F: FnOnce(&mut Parser<'_, K, E>),
+{
+  let tokens = lex(s);
+  let mut p = Parser::new(&tokens);
+  f(&mut p);
+  let mut sink = Render::default();
+  p.finish(&mut sink);
+  assert_eq!(sink.out, want);
+  assert_eq!(sink.errors, want_errors);
+}
+
+#[test]
+fn trivia() {
+  check(" (a  b) c", root, r#"Root(" "List("(""a""  ""b"")")" ""c")"#, &[]);
+  check("a ", root, r#"Root("a"" ")"#, &[]);
+}
+
+/// Puts comments on the same line after a node in that node, and doc comments in the node after
+/// them.
+struct Comments;
+
+impl TriviaPolicy<K> for Comments {
+  fn attach(&mut self, trivia: &[Token<'_, K>], idx: usize) -> Attach {
+    let tok = trivia[idx];
+    if tok.kind == K::DocComment {
+      Attach::Next
+    } else if trivia[..=idx].iter().all(|tok| !tok.text.contains('\n')) {
+      Attach::Previous
+    } else {
+      Attach::Parent
+    }
+  }
+}
+
+#[test]
+fn trivia_policy() {
+  let tokens = lex("(a) ; one\n;; two\n(b)\n");
+  let mut p = Parser::new(&tokens);
+  root(&mut p);
+  let mut sink = Render::default();
+  p.finish_with(&mut sink, &mut Comments);
+  let want = r#"Root(List("(""a"")"" ""; one")"\n"List(";; two""\n""(""b"")")"\n")"#;
+  assert_eq!(sink.out, want);
+}
+
+#[test]
+fn class_policy() {
+  let tokens = lex("(a) ; one\n;; two\n(b) ; three\n; four\nc");
+  let mut p = Parser::new(&tokens);
+  root(&mut p);
+  let mut sink = Render::default();
+  p.finish_with(&mut sink, &mut ClassPolicy);
+  let want = concat!(
+    r#"Root(List("(""a"")"" ""; one")"\n"List(";; two""\n""(""b"")"" ""; three")"#,
+    r#""\n""; four""\n""c")"#,
+  );
+  assert_eq!(sink.out, want);
+}
+
+#[test]
+fn class_policy_nested() {
+  let tokens = lex("a\n;; one\n;; two\nb");
+  let mut p = Parser::new(&tokens);
+  let root = p.enter();
+  p.bump();
+  let outer = p.enter();
+  let inner = p.enter();
+  p.bump();
+  p.exit(inner, K::Atom);
+  p.exit(outer, K::List);
+  p.exit(root, K::Root);
+  let mut sink = Render::default();
+  p.finish_with(&mut sink, &mut ClassPolicy);
+  let want = r#"Root("a""\n"List(";; one""\n"";; two""\n"Atom("b")))"#;
+  assert_eq!(sink.out, want);
+}
+
+#[test]
+fn trivia_only() {
+  check(" ; a\n", root, r#"Root(" ""; a""\n")"#, &[]);
+  for s in [" ; a\n", "\n(b) ; c\n"] {
+    let tokens = lex(s);
+    let mut p = Parser::new(&tokens);
+    root(&mut p);
+    let mut sink = Render::default();
+    p.finish_with(&mut sink, &mut ClassPolicy);
+    let want = if s.starts_with(' ') {
+      r#"Root(" ""; a""\n")"#
+    } else {
+      r#"Root("\n"List("(""b"")"" ""; c")"\n")"#
+    };
+    assert_eq!(sink.out, want);
+  }
+}
+
+#[test]
+fn default_policy_empty_node() {
+  let got = dump("a b", |p| {
+    let root = p.enter();
+    let en = p.enter();
+    p.bump();
+    let empty = p.enter();
+    p.exit(empty, K::Atom);
+    p.exit(en, K::List);
+    p.bump();
+    p.exit(root, K::Root);
+  });
+  let want = r#"Root@0..3
+  List@0..2
+    Name@0..1 "a"
+    Ws@1..2 " "
+  Name@2..3 "b"
+"#;
+  assert_eq!(got, want);
+}
+
+#[test]
+fn default_policy_trailing_trivia() {
+  let got = dump("((a) ) ", root);
+  let want = r#"Root@0..7
+  List@0..6
+    LRound@0..1 "("
+    List@1..4
+      LRound@1..2 "("
+      Name@2..3 "a"
+      RRound@3..4 ")"
+    Ws@4..5 " "
+    RRound@5..6 ")"
+  Ws@6..7 " "
+"#;
+  assert_eq!(got, want);
+}
+
+#[test]
+fn default_policy_error_next_to_trivia() {
+  let got = dump("a b", |p| {
+    let root = p.enter();
+    let en = p.enter();
+    p.bump();
+    p.exit(en, K::Atom);
+    p.error_tokens(E(vec![K::LRound]), 1);
+    p.bump();
+    p.exit(root, K::Root);
+  });
+  let want = r#"Root@0..3
+  Atom@0..1
+    Name@0..1 "a"
+  Ws@1..2 " "
+  error@2..3: expected LRound
+  Name@2..3 "b"
+"#;
+  assert_eq!(got, want);
+}
+
+#[test]
+fn eat_error() {
+  check("(a", root, r#"Root(List("(""a"))"#, &[E(vec![K::RRound])]);
+}
+
+fn dump<F>(s: &str, f: F) -> String
+where
+  F: FnOnce(&mut Parser<'_, K, E>),
+{
+  let tokens = lex(s);
+  let mut p = Parser::new(&tokens);
+  f(&mut p);
+  let mut sink = debug_sink::DebugSink::default();
+  p.finish(&mut sink);
+  sink.finish()
+}
+
+#[test]
+fn debug_sink_errors() {
+  let got = dump("(a (b", root);
+  let want = r#"Root@0..5
+  List@0..5
+    LRound@0..1 "("
+    Name@1..2 "a"
+    Ws@2..3 " "
+    List@3..5
+      LRound@3..4 "("
+      Name@4..5 "b"
+error@4..5: expected RRound
+error@4..5: expected RRound
+"#;
+  assert_eq!(got, want);
+  let got = dump(
+    "(a)",
+    with_rest(|p| {
+      p.eat(K::Name);
+    }),
+  );
+  let want = r#"Root@0..3
+  error@0..1: expected Name
+  LRound@0..1 "("
+  Name@1..2 "a"
+  RRound@2..3 ")"
+"#;
+  assert_eq!(got, want);
+}
+
+#[test]
+fn debug_sink_file() {
+  let got = dump(" (a  b) c", root);
+  debug_sink::check_file(
+    &got,
+    concat!(env!("CARGO_MANIFEST_DIR"), "/src/test-data/debug_sink.txt"),
+  );
+}
+
+#[test]
+fn error_tokens() {
+  let got = dump(" (a  b) c", with_rest(|p| p.error_tokens(E(vec![K::Name]), 2)));
+  let want = r#"Root@0..9
+  error@1..3: expected Name
+  Ws@0..1 " "
+  LRound@1..2 "("
+  Name@2..3 "a"
+  Ws@3..5 "  "
+  Name@5..6 "b"
+  RRound@6..7 ")"
+  Ws@7..8 " "
+  Name@8..9 "c"
+"#;
+  assert_eq!(got, want);
+}
+
+#[test]
+fn exit_with_error() {
+  let list = |p: &mut Parser<'_, K, E>| {
+    let en = p.enter();
+    p.bump();
+    p.bump();
+    p.exit_with_error(en, K::List, E(vec![K::RRound]));
+  };
+  let got = dump(" (a b", with_rest(list));
+  let want = r#"Root@0..5
+  Ws@0..1 " "
+  List@1..3
+    LRound@1..2 "("
+    Name@2..3 "a"
+    error@1..3: expected RRound
+  Ws@3..4 " "
+  Name@4..5 "b"
+"#;
+  assert_eq!(got, want);
+}
+
+/// Runs `f` at the start of a root node, then consumes the rest of the tokens.
+fn with_rest<F>(f: F) -> impl Fn(&mut Parser<'_, K, E>)
+where
+  F: Fn(&mut Parser<'_, K, E>),
+{
+  move |p| {
+    let en = p.enter();
+    f(p);
+    while p.peek().is_some() {
+      p.bump();
+    }
+    p.exit(en, K::Root);
+  }
+}
+
+#[test]
+fn recover_skip() {
+  let f = with_rest(|p| assert!(p.eat_or_recover(K::Name, &[K::RRound], K::Error).is_some()));
+  check("(( a)", f, r#"Root(Error("(""(")" ""a"")")"#, &[E(vec![K::Name])]);
+}
+
+#[test]
+fn recover_stop() {
+  let f = with_rest(|p| assert!(p.eat_or_recover(K::Name, &[K::RRound], K::Error).is_none()));
+  check(") a", f, r#"Root(")"" ""a")"#, &[E(vec![K::Name])]);
+}
+
+#[test]
+fn err_and_bump() {
+  let f = with_rest(|p| p.err_and_bump(E(vec![K::LRound]), K::Error));
+  check("a )", f, r#"Root(Error("a")" "")")"#, &[E(vec![K::LRound])]);
+}
+
+#[test]
+fn expected_one_of() {
+  check("(a", |p| assert!(p.eat(K::Name).is_none()), "", &[E(vec![K::Name])]);
+  check(")", sexp, "", &[E(vec![K::LRound, K::Name])]);
+}
+
+#[test]
+fn record_expected() {
+  let tokens = lex("(b a");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  p.record_expected_at(3);
+  root(&mut p);
+  assert_eq!(p.recorded_expected(), [K::LRound, K::Name]);
+  assert!(p.expected().is_empty());
+  p.finish(&mut Render::default());
+}
+
+#[test]
+fn record_expected_trivia() {
+  let tokens = lex("(b a");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  p.record_expected_at(2);
+  root(&mut p);
+  assert_eq!(p.recorded_expected(), [K::LRound, K::Name]);
+  p.finish(&mut Render::default());
+}
+
+const OPS: pratt::Ops<'static, K> = pratt::Ops {
+  infix: &[
+    pratt::Infix { op: K::Plus, left: 1, right: 2, node: K::Add },
+    pratt::Infix { op: K::Star, left: 3, right: 4, node: K::Mul },
+    pratt::Infix { op: K::Caret, left: 8, right: 7, node: K::Pow },
+  ],
+  prefix: &[pratt::Prefix { op: K::Minus, right: 5, node: K::Neg }],
+  postfix: &[pratt::Postfix { op: K::Question, left: 9, node: K::Try }],
+};
+
+fn atom(p: &mut Parser<'_, K, E>) -> Option<Exited> {
+  let en = p.enter();
+  if p.at(K::Name) {
+    p.bump();
+    Some(p.exit(en, K::Atom))
+  } else if p.at(K::LRound) {
+    p.bump();
+    expr(p);
+    p.eat(K::RRound);
+    Some(p.exit(en, K::List))
+  } else {
+    p.error_expected();
+    p.abandon(en);
+    None
+  }
+}
+
+fn expr(p: &mut Parser<'_, K, E>) -> Option<Exited> {
+  p.pratt(&OPS, 0, &mut atom)
+}
+
+fn check_expr(s: &str, want: &str) {
+  check(s, |p| assert!(expr(p).is_some()), want, &[]);
+}
+
+#[test]
+fn pratt_left_assoc() {
+  check_expr("a+b+c", r#"Add(Add(Atom("a")"+"Atom("b"))"+"Atom("c"))"#);
+}
+
+#[test]
+fn pratt_right_assoc() {
+  check_expr("a^b^c", r#"Pow(Atom("a")"^"Pow(Atom("b")"^"Atom("c")))"#);
+}
+
+#[test]
+fn pratt_precedence() {
+  check_expr("a+b*c", r#"Add(Atom("a")"+"Mul(Atom("b")"*"Atom("c")))"#);
+  check_expr("a*b+c", r#"Add(Mul(Atom("a")"*"Atom("b"))"+"Atom("c"))"#);
+  check_expr("a*(b+c)", r#"Mul(Atom("a")"*"List("("Add(Atom("b")"+"Atom("c"))")"))"#);
+}
+
+#[test]
+fn pratt_prefix_postfix() {
+  check_expr("-a?", r#"Neg("-"Try(Atom("a")"?"))"#);
+  check_expr("-a+b", r#"Add(Neg("-"Atom("a"))"+"Atom("b"))"#);
+  check_expr("-a^b", r#"Neg("-"Pow(Atom("a")"^"Atom("b")))"#);
+}
+
+#[test]
+fn pratt_missing_operand() {
+  check(
+    "a+",
+    |p| assert!(expr(p).is_some()),
+    r#"Add(Atom("a")"+")"#,
+    &[E(vec![K::Minus, K::Name, K::LRound])],
+  );
+}
+
+#[test]
+fn pratt_expected_ops() {
+  check(
+    "(a b",
+    with_rest(|p| assert!(expr(p).is_some())),
+    r#"Root(List("("Atom("a"))" ""b")"#,
+    &[E(vec![K::Question, K::Plus, K::Star, K::Caret, K::RRound])],
+  );
+}
+
+/// Tries to parse an expression, falling back to just consuming everything if there was an error.
+fn try_expr(p: &mut Parser<'_, K, E>) {
+  let en = p.enter();
+  let save = p.save();
+  expr(p);
+  if !p.ok_since(save) {
+    while p.peek().is_some() {
+      p.bump();
+    }
+  }
+  p.exit(en, K::Root);
+}
+
+#[test]
+fn pratt_save_ok() {
+  check("a + b", try_expr, r#"Root(Add(Atom("a")" ""+"" "Atom("b")))"#, &[]);
+}
+
+#[test]
+fn pratt_save_backtrack() {
+  check("a + * b", try_expr, r#"Root("a"" ""+"" ""*"" ""b")"#, &[]);
+}
+
+/// Parses a name followed by a `+`, rewinding if there was no `+`, or else parses an expression.
+fn try_name_plus(p: &mut Parser<'_, K, E>) {
+  let en = p.enter();
+  let name = p.try_parse(|p| {
+    let en = p.enter();
+    p.eat(K::Name);
+    if p.eat(K::Plus).is_none() {
+      p.abandon(en);
+      return None;
+    }
+    Some(p.exit(en, K::Add))
+  });
+  if name.is_none() {
+    expr(p);
+  }
+  p.exit(en, K::Root);
+}
+
+#[test]
+fn try_parse_some() {
+  check("a +", try_name_plus, r#"Root(Add("a"" ""+"))"#, &[]);
+}
+
+#[test]
+fn try_parse_none() {
+  check("a * b", try_name_plus, r#"Root(Mul(Atom("a")" ""*"" "Atom("b")))"#, &[]);
+}
+
+#[test]
+fn try_parse_keeps_errors() {
+  let f = |p: &mut Parser<'_, K, E>| {
+    let en = p.enter();
+    let got = p.try_parse(|p| {
+      p.error(E(vec![]));
+      p.bump();
+      Some(())
+    });
+    assert!(got.is_some());
+    p.exit(en, K::Root);
+  };
+  check("a", f, r#"Root("a")"#, &[E(vec![])]);
+}
+
+#[test]
+fn restore() {
+  let tokens = lex("a b");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  let en = p.enter();
+  let save = p.save();
+  p.bump();
+  let inner = p.enter();
+  p.bump();
+  p.exit(inner, K::Atom);
+  p.restore(save);
+  assert_eq!(p.peek().unwrap().text, "a");
+  p.bump();
+  p.bump();
+  p.exit(en, K::Root);
+  let mut sink = Tokens::default();
+  p.finish(&mut sink);
+  assert_eq!(sink.get(), [(K::Name, "a"), (K::Ws, " "), (K::Name, "b")]);
+}
+
+#[test]
+#[should_panic = "changed a marker from before the latest save"]
+fn exit_before_save() {
+  let tokens = lex("a");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  let en = p.enter();
+  let save = p.save();
+  p.bump();
+  p.exit(en, K::Root);
+  p.restore(save);
+}
+
+#[test]
+fn flush_keeps_save() {
+  let tokens = lex("a b");
+  let mut p = Parser::<'_, K, E>::new_streaming(tokens.iter().copied(), 1);
+  let mut sink = Tokens::default();
+  let en = p.enter();
+  p.bump();
+  p.exit(en, K::Atom);
+  let save = p.save();
+  let en = p.enter();
+  p.bump();
+  p.exit(en, K::Atom);
+  p.flush(&mut sink);
+  p.restore(save);
+  assert_eq!(p.peek().unwrap().text, "b");
+  let en = p.enter();
+  p.bump();
+  p.exit(en, K::Name);
+  p.finish(&mut sink);
+  assert_eq!(sink.get(), [(K::Name, "a"), (K::Ws, " "), (K::Name, "b")]);
+}
+
+#[test]
+fn dropped_save() {
+  let tokens = lex("a b");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  let mut sink = Tokens::default();
+  let root = p.enter_with(K::Root);
+  let en = p.enter();
+  drop(p.save());
+  p.bump();
+  // the dropped save neither stops changing markers from before it, nor stops flushing.
+  p.exit(en, K::Atom);
+  p.flush(&mut sink);
+  assert_eq!(sink.get(), [(K::Name, "a"), (K::Ws, " ")]);
+  let save = p.save();
+  p.bump();
+  p.restore(save);
+  p.bump();
+  p.exit(root, K::Root);
+  p.finish(&mut sink);
+  assert_eq!(sink.get(), [(K::Name, "a"), (K::Ws, " "), (K::Name, "b")]);
+}
+
+/// Checks that parsing `s` with `root` gives the same result when streaming and flushing after each
+/// top-level item, and that few tokens are kept in memory.
+fn check_streaming(s: &str) {
+  let tokens = lex(s);
+  let mut p = Parser::new(&tokens);
+  root(&mut p);
+  let mut want = Render::default();
+  p.finish(&mut want);
+  let mut p = Parser::new_streaming(lex(s), 1);
+  let mut got = Render::default();
+  let en = p.enter_with(K::Root);
+  while p.peek().is_some() {
+    sexp(&mut p);
+    p.flush(&mut got);
+    let Source::Iter { buf, .. } = &p.tokens else { unreachable!("not streaming") };
+    assert!(buf.len() <= 2, "kept {} tokens", buf.len());
+  }
+  p.exit(en, K::Root);
+  p.finish(&mut got);
+  assert_eq!(got.out, want.out);
+  assert_eq!(got.errors, want.errors);
+}
+
+#[test]
+fn streaming() {
+  check_streaming(" (a  b) c (d (e f)) g");
+  check_streaming("(a (b");
+  check_streaming("");
+}
+
+#[test]
+fn parser_is_send() {
+  fn assert_send<T: Send>() {}
+  assert_send::<Parser<'static, u8, ()>>();
+}
+
+/// Gives `a`, then nothing, then `b`.
+struct NotFused(usize);
+
+impl Iterator for NotFused {
+  type Item = Token<'static, K>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.0 += 1;
+    match self.0 {
+      1 => Some(Token { kind: K::Name, text: "a" }),
+      3 => Some(Token { kind: K::Name, text: "b" }),
+      _ => None,
+    }
+  }
+}
+
+#[test]
+fn streaming_fused() {
+  let mut p = Parser::<'_, K, E>::new_streaming(NotFused(0), 1);
+  p.bump();
+  assert!(p.peek().is_none());
+  assert!(p.peek().is_none());
+}
+
+#[test]
+#[should_panic = "marker was already flushed"]
+fn precede_flushed() {
+  let tokens = lex("a b");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  let root = p.enter_with(K::Root);
+  let en = p.enter();
+  p.bump();
+  let ex = p.exit(en, K::Atom);
+  p.flush(&mut Render::default());
+  let en = p.precede(ex);
+  p.bump();
+  p.exit(en, K::Add);
+  p.exit(root, K::Root);
+}
+
+#[test]
+#[should_panic = "marker was already flushed"]
+fn abandon_flushed() {
+  let tokens = lex("a b");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  let root = p.enter_with(K::Root);
+  p.bump();
+  p.flush(&mut Render::default());
+  p.abandon(root);
+}
+
+impl Encode for K {
+  fn encode(&self, buf: &mut Vec<u8>) {
+    (*self as u16).encode(buf);
+  }
+
+  fn decode(bytes: &mut &[u8]) -> Option<Self> {
+    K::ALL.get(usize::decode(bytes)?).copied()
+  }
+}
+
+impl Encode for E {
+  fn encode(&self, buf: &mut Vec<u8>) {
+    self.0.len().encode(buf);
+    for kind in &self.0 {
+      kind.encode(buf);
+    }
+  }
+
+  fn decode(bytes: &mut &[u8]) -> Option<Self> {
+    let len = usize::decode(bytes)?;
+    (0..len).map(|_| K::decode(bytes)).collect::<Option<_>>().map(E)
+  }
+}
+
+/// Checks that the events from parsing `s` with `f` replay into the same tree as finishing, and
+/// survive serialization.
+fn check_replay<F>(s: &str, f: F)
+where
+  F: Fn(&mut Parser<'_, K, E>),
+{
+  let tokens = lex(s);
+  let mut p = Parser::new(&tokens);
+  f(&mut p);
+  let mut want = Render::default();
+  p.finish(&mut want);
+  let mut p = Parser::new(&tokens);
+  f(&mut p);
+  let events = p.into_events();
+  for _ in 0..2 {
+    let mut got = Render::default();
+    event_log::replay(&events, &tokens, &mut got);
+    assert_eq!(got.out, want.out);
+    assert_eq!(got.errors, want.errors);
+  }
+  let bytes = event_log::encode(&events);
+  assert_eq!(event_log::decode::<K, E>(&bytes).as_ref(), Some(&events));
+  let mut got = Render::default();
+  event_log::into_replay(events, &tokens, &mut got);
+  assert_eq!(got.out, want.out);
+  assert_eq!(got.errors, want.errors);
+}
+
+/// An error which can't be cloned, and a sink which counts them.
+struct NoClone;
+
+#[derive(Default)]
+struct CountErrors(usize);
+
+impl Sink<K, NoClone> for CountErrors {
+  fn enter(&mut self, _: K) {}
+
+  fn token(&mut self, _: Token<'_, K>) {}
+
+  fn exit(&mut self) {}
+
+  fn error(&mut self, NoClone: NoClone) {
+    self.0 += 1;
+  }
+}
+
+#[test]
+fn into_replay_no_clone() {
+  let tokens = lex("a");
+  let events = vec![
+    event_log::Event::Enter(K::Root),
+    event_log::Event::Error(NoClone),
+    event_log::Event::Token(None),
+    event_log::Event::Exit,
+  ];
+  let mut sink = CountErrors::default();
+  event_log::into_replay(events, &tokens, &mut sink);
+  assert_eq!(sink.0, 1);
+}
+
+#[test]
+fn replay() {
+  check_replay(" (a  b) c (d (e", root);
+  check_replay(" -a + b * c? d", with_rest(|p| assert!(expr(p).is_some())));
+  check_replay("a b", with_rest(|p| p.error_tokens(E(vec![K::Name]), 2)));
+  check_replay(
+    "(a b",
+    with_rest(|p| {
+      let en = p.enter();
+      p.bump();
+      p.bump_remap(K::Atom);
+      p.exit_with_error(en, K::List, E(vec![K::RRound]));
+    }),
+  );
+  check_replay("", root);
+}
+
+#[test]
+fn decode_invalid() {
+  assert_eq!(event_log::decode::<K, E>(&[]), None);
+  assert_eq!(event_log::decode::<K, E>(&[0, 99]), None);
+  assert_eq!(event_log::decode::<K, E>(&[0, 0]), None);
+  assert_eq!(event_log::decode::<K, E>(&[0, 0, 100]), None);
+}
+
+#[test]
+fn decode_u64() {
+  let mut buf = Vec::new();
+  u64::MAX.encode(&mut buf);
+  assert_eq!(buf, [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+  assert_eq!(u64::decode(&mut buf.as_slice()), Some(u64::MAX));
+  // the tenth byte has bits past the 64th.
+  let too_big = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x03];
+  assert_eq!(u64::decode(&mut too_big.as_slice()), None);
+}
+
+#[test]
+fn contextual_keyword() {
+  let f = |p: &mut Parser<'_, K, E>| {
+    let en = p.enter();
+    while p.peek().is_some() {
+      if p.at_contextual("neg") {
+        assert_eq!(p.bump_remap(K::Minus).kind, K::Minus);
+      } else {
+        p.bump();
+      }
+    }
+    p.exit(en, K::Root);
+  };
+  let tokens = lex("a neg");
+  let mut p = Parser::new(&tokens);
+  f(&mut p);
+  let mut sink = Tokens::default();
+  p.finish(&mut sink);
+  assert_eq!(sink.get(), [(K::Name, "a"), (K::Ws, " "), (K::Minus, "neg")]);
+}
+
+#[test]
+fn split() {
+  let tokens = lex("abc d");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  let en = p.enter();
+  assert_eq!(p.bump_split(1, K::Plus, K::Name).text, "a");
+  let save = p.save();
+  assert_eq!(p.bump_split(1, K::Minus, K::Star).text, "b");
+  assert_eq!(p.peek().unwrap(), Token { kind: K::Star, text: "c" });
+  p.error(E(vec![]));
+  assert!(!p.ok_since(save));
+  assert_eq!(p.peek().unwrap(), Token { kind: K::Name, text: "bc" });
+  assert_eq!(p.bump_split(1, K::Minus, K::Star).text, "b");
+  assert_eq!(p.bump(), Token { kind: K::Star, text: "c" });
+  assert_eq!(p.bump(), Token { kind: K::Name, text: "d" });
+  p.exit(en, K::Root);
+  let mut sink = Tokens::default();
+  p.finish(&mut sink);
+  let want = [(K::Plus, "a"), (K::Minus, "b"), (K::Star, "c"), (K::Ws, " "), (K::Name, "d")];
+  assert_eq!(sink.get(), want);
+}
+
+/// Collects the tokens in a tree.
+#[derive(Debug, Default)]
+struct Tokens(Vec<(K, String)>);
+
+impl Tokens {
+  fn get(&self) -> Vec<(K, &str)> {
+    self.0.iter().map(|(k, s)| (*k, s.as_str())).collect()
+  }
+}
+
+impl Sink<K, E> for Tokens {
+  fn enter(&mut self, _: K) {}
+
+  fn token(&mut self, token: Token<'_, K>) {
+    self.0.push((token.kind, token.text.to_owned()));
+  }
+
+  fn exit(&mut self) {}
+
+  fn error(&mut self, _: E) {}
+}
+
+#[test]
+#[should_panic = "parser is stuck at token 2 (\"b\")"]
+fn out_of_fuel() {
+  let tokens = lex("a b");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  p.set_fuel(10);
+  p.bump();
+  while p.at(K::Name) {}
+}
+
+#[test]
+fn fuel_is_looks() {
+  let tokens = lex("a b");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  p.set_fuel(2);
+  assert!(p.at(K::Name));
+  assert!(p.at(K::Name));
+  p.bump();
+  assert!(!p.at(K::LRound));
+  assert!(p.at(K::Name));
+}
+
+#[test]
+#[should_panic = "fuel must not be 0"]
+fn zero_fuel() {
+  let tokens = lex("a");
+  Parser::<'_, K, E>::new(&tokens).set_fuel(0);
+}
+
+#[cfg(feature = "cst")]
+mod cst {
+  use super::{E, K, Parser, lex, root};
+  use crate::cst::{CstSink, Element, Tree};
+
+  fn parse(s: &str) -> (Tree<K>, Vec<crate::cst::Error<K, E>>) {
+    let tokens = lex(s);
+    let mut p = Parser::new(&tokens);
+    root(&mut p);
+    let mut sink = CstSink::default();
+    p.finish(&mut sink);
+    sink.finish()
+  }
+
+  #[test]
+  fn navigate() {
+    let (tree, errors) = parse("(a (b c)) d");
+    assert!(errors.is_empty());
+    let root = tree.root();
+    assert_eq!(tree.kind(root), K::Root);
+    assert_eq!(tree.parent(root), None);
+    assert_eq!(tree.node_text(root), "(a (b c)) d");
+    let outer: Vec<_> = tree.child_nodes(root).collect();
+    let &[outer] = outer.as_slice() else { panic!("wanted one child node, got {outer:?}") };
+    assert_eq!(tree.parent(outer), Some(root));
+    let children: Vec<_> = tree
+      .children(outer)
+      .map(|elem| match elem {
+        Element::Node(node) => (tree.kind(node), tree.node_text(node)),
+        Element::Token(tok) => (tree.token_kind(tok), tree.token_text(tok)),
+      })
+      .collect();
+    assert_eq!(
+      children,
+      [(K::LRound, "("), (K::Name, "a"), (K::Ws, " "), (K::List, "(b c)"), (K::RRound, ")")]
+    );
+    let inner = tree.first_child(outer).unwrap();
+    assert_eq!(tree.range(inner), 3..8);
+    assert_eq!(tree.next_sibling(inner), None);
+    assert!(tree.tokens(inner).all(|tok| tree.token_parent(tok) == inner));
+    let last = tree.tokens(root).last().unwrap();
+    assert_eq!((tree.token_text(last), tree.token_parent(last)), ("d", root));
+  }
+
+  #[test]
+  fn errors() {
+    let (_, errors) = parse("(a");
+    let got: Vec<_> = errors.into_iter().map(|e| (e.range, e.kind, e.inner)).collect();
+    assert_eq!(got, [(1..2, Some(K::Name), E(vec![K::RRound]))]);
+  }
+
+  #[test]
+  fn errors_in_order() {
+    let tokens = lex("(a b");
+    let mut p = Parser::<'_, K, E>::new(&tokens);
+    let root = p.enter();
+    let en = p.enter();
+    p.bump();
+    p.error(E(vec![K::LRound]));
+    p.bump();
+    p.exit_with_error(en, K::List, E(vec![K::RRound]));
+    p.bump();
+    p.exit(root, K::Root);
+    let mut sink = CstSink::default();
+    p.finish(&mut sink);
+    let (_, errors) = sink.finish();
+    let got: Vec<_> = errors.into_iter().map(|e| (e.range, e.inner)).collect();
+    assert_eq!(got, [(0..2, E(vec![K::RRound])), (1..2, E(vec![K::LRound]))]);
+  }
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn stats() {
+  let f = with_rest(|p| {
+    assert!(p.try_parse(|p| p.bump().text.is_empty().then_some(())).is_none());
+    let en = p.enter();
+    p.peek_n(2);
+    p.bump();
+    p.exit(en, K::Atom);
+  });
+  let tokens = lex("a * b");
+  let mut p = Parser::new(&tokens);
+  f(&mut p);
+  let want = "\
+events: 8
+backtracks: 1
+nodes:
+  Atom: 1 (max peek 2)
+  Root: 1 (max peek 0)
+";
+  assert_eq!(p.stats().to_string(), want);
+}
+
+/// Needs no bounds on the kinds, even with the `stats` feature.
+#[cfg(feature = "stats")]
+fn exit_any<K, E>(p: &mut Parser<'_, K, E>, kind: K) {
+  let en = p.enter();
+  p.exit(en, kind);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn stats_flushed() {
+  let tokens = lex("a b");
+  let mut p = Parser::<'_, K, E>::new(&tokens);
+  let root = p.enter_with(K::Root);
+  p.bump();
+  exit_any(&mut p, K::Atom);
+  let mut sink = Render::default();
+  p.flush(&mut sink);
+  let en = p.enter();
+  p.bump();
+  p.exit(en, K::Atom);
+  p.exit(root, K::List);
+  let save = p.save();
+  exit_any(&mut p, K::Error);
+  p.restore(save);
+  let want = "\
+events: 10
+backtracks: 1
+nodes:
+  Atom: 2 (max peek 0)
+  Root: 1 (max peek 0)
+  Error: 1 (max peek 0)
+";
+  assert_eq!(p.stats().to_string(), want);
+}
+
+#[cfg(feature = "rowan")]
+mod rowan {
+  use super::{E, K, Parser, lex, list_contents, root, with_rest};
+  use crate::reparse;
+  use crate::rowan_sink::{self, RowanSink, RowanSinkWithCache, ToDiagnostic};
+  use crate::trivia::ClassPolicy;
+  use diagnostic::Code;
+
+  #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+  enum Lang {}
+
+  impl ::rowan::Language for Lang {
+    type Kind = K;
+
+    fn kind_from_raw(raw: ::rowan::SyntaxKind) -> K {
+      K::ALL[usize::from(raw.0)]
+    }
+
+    fn kind_to_raw(kind: K) -> ::rowan::SyntaxKind {
+      kind.into()
+    }
+  }
+
+  impl From<K> for ::rowan::SyntaxKind {
+    fn from(kind: K) -> Self {
+      Self(kind as u16)
+    }
+  }
+
+  type SyntaxNode = ::rowan::SyntaxNode<Lang>;
+
+  fn parse(s: &str) -> SyntaxNode {
+    parse_with(s, RowanSink::default())
+  }
+
+  fn parse_with(s: &str, mut sink: RowanSinkWithCache<'_, K, E>) -> SyntaxNode {
+    let tokens = lex(s);
+    let mut p = Parser::new(&tokens);
+    root(&mut p);
+    p.finish(&mut sink);
+    let (node, errors) = sink.finish::<Lang>();
+    assert!(errors.is_empty());
+    node
+  }
+
+  /// Returns the number of distinct green nodes and tokens in the trees.
+  fn distinct_green(roots: &[SyntaxNode]) -> usize {
+    let mut seen = std::collections::HashSet::<*const ()>::new();
+    for elem in roots.iter().flat_map(SyntaxNode::descendants_with_tokens) {
+      let ptr = match elem {
+        ::rowan::NodeOrToken::Node(node) => std::ptr::from_ref(&*node.green()).cast(),
+        ::rowan::NodeOrToken::Token(token) => std::ptr::from_ref(token.green()).cast(),
+      };
+      seen.insert(ptr);
+    }
+    seen.len()
+  }
+
+  #[test]
+  fn shared_cache() {
+    let files = ["(a (b c)) (b c)", "(b c) d", "(a (b c)) d"];
+    let separate: Vec<_> = files.iter().map(|s| parse(s)).collect();
+    let mut cache = ::rowan::NodeCache::default();
+    let shared: Vec<_> =
+      files.iter().map(|s| parse_with(s, RowanSinkWithCache::with_cache(&mut cache))).collect();
+    assert_eq!(separate.iter().map(ToString::to_string).collect::<Vec<_>>(), files);
+    assert_eq!(shared.iter().map(ToString::to_string).collect::<Vec<_>>(), files);
+    assert_eq!((distinct_green(&separate), distinct_green(&shared)), (28, 16));
+  }
+
+  fn reparser(kind: K) -> Option<reparse::Reparser<K, E>> {
+    match kind {
+      K::List => Some(|p| {
+        p.eat(K::LRound);
+        list_contents(p);
+      }),
+      _ => None,
+    }
+  }
+
+  fn check_reparse(old: &str, (start, end, text): (u32, u32, &str), want: Option<(u32, u32)>) {
+    let change = || {
+      let pos = |col| text_pos::PositionUtf16 { line: 0, col };
+      let range = text_pos::RangeUtf16 { start: pos(start), end: pos(end) };
+      apply_changes::Change { range: Some(range), text: text.to_owned() }
+    };
+    let old_root = parse(old);
+    let mut new = old.to_owned();
+    apply_changes::get(&mut new, vec![change()]);
+    let tokens = lex(&new);
+    let pos_db = text_pos::PositionDb::new(old);
+    let reparsed = reparse::get(&old_root, &pos_db, &change(), &tokens, reparser);
+    match (reparsed, want) {
+      (None, None) => {}
+      (Some(reparsed), Some((start, end))) => {
+        assert_eq!(reparsed.old_range, ::rowan::TextRange::new(start.into(), end.into()));
+        assert_eq!(reparsed.root.to_string(), new);
+        assert_eq!(format!("{:#?}", reparsed.root), format!("{:#?}", parse(&new)));
+        assert!(reparsed.errors.is_empty());
+      }
+      (got, want) => panic!("got {got:?}, want {want:?}"),
+    }
+  }
+
+  #[test]
+  fn error_range() {
+    let tokens = lex("(a b) c");
+    let mut p = Parser::new(&tokens);
+    with_rest(|p| {
+      let en = p.enter();
+      p.bump();
+      p.error_tokens(E(vec![K::RRound]), 1);
+      p.bump();
+      p.bump();
+      p.exit_with_error(en, K::List, E(vec![K::Name]));
+    })(&mut p);
+    let mut sink: RowanSink<K, E> = RowanSink::default();
+    p.finish(&mut sink);
+    let (_, errors) = sink.finish::<Lang>();
+    let got: Vec<_> = errors
+      .into_iter()
+      .map(|e| (u32::from(e.range.start()), u32::from(e.range.end()), e.kind, e.inner))
+      .collect();
+    assert_eq!(got, [(0, 4, None, E(vec![K::Name])), (1, 2, None, E(vec![K::RRound]))]);
+  }
+
+  impl ToDiagnostic<K> for E {
+    fn message(&self, found: Option<&K>) -> String {
+      match found {
+        Some(kind) => format!("{self}, found {kind}"),
+        None => self.to_string(),
+      }
+    }
+
+    fn code(&self) -> Code {
+      Code::n(1)
+    }
+  }
+
+  #[test]
+  fn diagnostics() {
+    let s = "(a\n(bc";
+    let tokens = lex(s);
+    let mut p = Parser::new(&tokens);
+    root(&mut p);
+    let mut sink = RowanSink::default();
+    p.finish(&mut sink);
+    let (_, errors) = sink.finish::<Lang>();
+    let got: Vec<_> = rowan_sink::diagnostics(&text_pos::PositionDb::new(s), &errors)
+      .iter()
+      .map(|d| (d.to_string(), d.code))
+      .collect();
+    let want = ("2:2-4: error: expected RRound, found Name".to_owned(), Code::n(1));
+    assert_eq!(got, [want.clone(), want]);
+    // out of bounds for the text, so at the end of it.
+    let got: Vec<_> = rowan_sink::diagnostics(&text_pos::PositionDb::new("(a"), &errors)
+      .iter()
+      .map(ToString::to_string)
+      .collect();
+    let want = "1:3-3: error: expected RRound, found Name";
+    assert_eq!(got, [want, want]);
+  }
+
+  #[test]
+  fn reparse_class_policy() {
+    let parse = |s: &str| {
+      let tokens = lex(s);
+      let mut p = Parser::new(&tokens);
+      root(&mut p);
+      let mut sink = RowanSink::default();
+      p.finish_with(&mut sink, &mut ClassPolicy);
+      sink.finish::<Lang>().0
+    };
+    let old = "(a (b (c) ; one\nd))";
+    let pos = |col| text_pos::PositionUtf16 { line: 1, col };
+    let range = text_pos::RangeUtf16 { start: pos(0), end: pos(1) };
+    let change = || apply_changes::Change { range: Some(range), text: "e f".to_owned() };
+    let mut new = old.to_owned();
+    apply_changes::get(&mut new, vec![change()]);
+    let tokens = lex(&new);
+    let mut cache = ::rowan::NodeCache::default();
+    let reparsed: reparse::Reparsed<Lang, E> = reparse::get_with(
+      &parse(old),
+      &text_pos::PositionDb::new(old),
+      &change(),
+      &tokens,
+      reparser,
+      &mut cache,
+      &mut ClassPolicy,
+    )
+    .unwrap();
+    assert_eq!(reparsed.old_range, ::rowan::TextRange::new(3.into(), 18.into()));
+    assert_eq!(format!("{:#?}", reparsed.root), format!("{:#?}", parse(&new)));
+  }
+
+  #[test]
+  fn reparse_inner() {
+    check_reparse("(a (b c) d)", (6, 7, "x y"), Some((3, 8)));
+  }
+
+  #[test]
+  fn reparse_outer() {
+    check_reparse("(a (b c) d)", (1, 2, "(z)"), Some((0, 11)));
+  }
+
+  #[test]
+  fn reparse_unbalanced() {
+    check_reparse("(a (b c) d)", (6, 7, ")"), None);
+  }
+
+  #[test]
+  fn splice_errors() {
+    let old = "(a (b c))";
+    let tokens = lex(old);
+    let mut p = Parser::new(&tokens);
+    let root = p.enter();
+    let en = p.enter();
+    p.bump();
+    list_contents(&mut p);
+    p.exit_with_error(en, K::List, E(vec![K::Name]));
+    p.exit(root, K::Root);
+    let mut sink = RowanSink::default();
+    p.finish(&mut sink);
+    let (old_root, mut old_errors) = sink.finish::<Lang>();
+    let range = |start: u32, end: u32| ::rowan::TextRange::new(start.into(), end.into());
+    // as if from the inner node, which will be reparsed.
+    old_errors.push(rowan_sink::Error { range: range(6, 7), kind: None, inner: E(vec![]) });
+    let pos = |col| text_pos::PositionUtf16 { line: 0, col };
+    let change = apply_changes::Change {
+      range: Some(text_pos::RangeUtf16 { start: pos(6), end: pos(7) }),
+      text: "x y".to_owned(),
+    };
+    let tokens = lex("(a (b x y))");
+    let pos_db = text_pos::PositionDb::new(old);
+    let reparsed = reparse::get(&old_root, &pos_db, &change, &tokens, reparser).unwrap();
+    assert_eq!(reparsed.old_range, range(3, 8));
+    let got: Vec<_> = reparsed.splice_errors(old_errors).into_iter().map(|e| e.range).collect();
+    assert_eq!(got, [range(0, 11)]);
+  }
+
+  #[test]
+  fn reparse_root() {
+    check_reparse("a (b c)", (0, 1, "z"), None);
+  }
+}
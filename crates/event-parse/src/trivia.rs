@@ -0,0 +1,96 @@
+//! Deciding where trivia goes in the tree.
+//!
+//! Between any two non-trivia tokens, there is a run of zero or more trivia tokens. Some nodes may
+//! end before the run, and some may start after it. Each trivia token in the run may go:
+//!
+//! - at the end of the outermost node that ended before the run, as with a comment on the same line
+//!   after an item,
+//! - in the node containing the run, or
+//! - at the start of the outermost node that starts after the run, as with a doc comment before an
+//!   item.
+//!
+//! See [`Parser::finish_with`](crate::Parser::finish_with).
+
+use token::{Token, TriviaClass, Triviable};
+
+/// Where a trivia token goes.
+///
+/// These are ordered by where they are in the text, so that `Previous < Parent < Next`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Attach {
+  /// At the end of the outermost node that ended before the trivia.
+  Previous,
+  /// In the node containing the trivia.
+  Parent,
+  /// At the start of the outermost node that starts after the trivia.
+  Next,
+}
+
+/// Decides where trivia goes.
+pub trait TriviaPolicy<K> {
+  /// Returns where `trivia[idx]` should go.
+  ///
+  /// `trivia` is a whole run of trivia tokens. Since the trivia stays in order, a token goes no
+  /// earlier than the tokens before it in the run, so e.g. if one token goes in the
+  /// [`Attach::Parent`], any later token that should go in the [`Attach::Previous`] goes in the
+  /// parent too.
+  ///
+  /// If there is no such node as the one returned, e.g. if it returned [`Attach::Previous`] but no
+  /// nodes ended before the trivia, the trivia goes in the parent. At the very start and end of the
+  /// input, where there is no parent, it goes in the root.
+  fn attach(&mut self, trivia: &[Token<'_, K>], idx: usize) -> Attach;
+
+  /// Returns whether to write trivia as soon as the parser enters a node inside another node, exits
+  /// a node inside the root, or consumes a token, into the node it is in then, instead of asking
+  /// [`TriviaPolicy::attach`].
+  ///
+  /// Defaults to `false`.
+  fn eager(&self) -> bool {
+    false
+  }
+}
+
+/// The default policy, which puts trivia in the node the parser is in when it gets past the trivia.
+///
+/// This is [eager](TriviaPolicy::eager), so trivia may go at the end of an empty node if the parser
+/// enters a node inside it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultPolicy;
+
+impl<K> TriviaPolicy<K> for DefaultPolicy {
+  fn attach(&mut self, _: &[Token<'_, K>], _: usize) -> Attach {
+    Attach::Parent
+  }
+
+  fn eager(&self) -> bool {
+    true
+  }
+}
+
+/// A policy based on the [`TriviaClass`] of each trivia token.
+///
+/// - Doc comments go at the start of the next node.
+/// - Comments on the same line as the end of the previous node go at the end of it, together with
+///   the whitespace before them.
+/// - Everything else goes in the parent.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ClassPolicy;
+
+impl<K> TriviaPolicy<K> for ClassPolicy
+where
+  K: Triviable,
+{
+  fn attach(&mut self, trivia: &[Token<'_, K>], idx: usize) -> Attach {
+    let is = |tok: &Token<'_, K>, class| tok.trivia_class() == Some(class);
+    let (line, _) = trivia.split_at(
+      trivia.iter().position(|tok| is(tok, TriviaClass::Newline)).unwrap_or(trivia.len()),
+    );
+    if is(&trivia[idx], TriviaClass::DocComment) {
+      Attach::Next
+    } else if idx < line.len() && line[idx..].iter().any(|tok| is(tok, TriviaClass::Comment)) {
+      Attach::Previous
+    } else {
+      Attach::Parent
+    }
+  }
+}
@@ -0,0 +1,42 @@
+//! Generates the code for the toy language.
+
+use std::collections::HashMap;
+
+fn main() {
+  let doc = HashMap::from([("fn", "Starts a function.")]);
+  let special = HashMap::from([("Name", "a name"), ("Int", "an integer")]);
+  let trivia = ["Whitespace", "LineComment", "DocComment", "Invalid"];
+  let opts = syntax_gen::Options {
+    comments: &["LineComment"],
+    doc_comments: &["DocComment"],
+    parser: Some(syntax_gen::ParserOptions { overrides: &["Expr"] }),
+    lexer: Some(syntax_gen::LexOptions {
+      whitespace: "Whitespace",
+      line_comment: Some(("//", "LineComment")),
+      block_comment: None,
+      string: None,
+      number: Some("Int"),
+      ident: "Name",
+      ident_start: None,
+      ident_continue: Some("super::ident_continue"),
+      hooks: &["super::doc_comment"],
+      error: "Invalid",
+    }),
+    make: Some(syntax_gen::MakeOptions {
+      whitespace: "Whitespace",
+      space: " ",
+      no_space_before: &[";", ",", ")", "("],
+      no_space_after: &["("],
+    }),
+    visit: Some(syntax_gen::VisitOptions { fold: true }),
+    ..syntax_gen::Options::new(
+      "Toy",
+      &trivia,
+      include_str!("src/toy.ungram"),
+      &doc,
+      &special,
+      file!(),
+    )
+  };
+  syntax_gen::get(&opts).unwrap();
+}
@@ -0,0 +1,118 @@
+//! Tests for the code generated by syntax-gen, for a toy language in `toy.ungram`.
+//!
+//! The generated code is not held to the lints of this workspace, since it is meant for other
+//! crates.
+
+#[cfg(test)]
+mod tests;
+
+#[allow(missing_docs, clippy::pedantic)]
+pub mod kind {
+  include!(concat!(env!("OUT_DIR"), "/kind.rs"));
+}
+
+#[allow(missing_docs, missing_debug_implementations, non_snake_case, clippy::pedantic)]
+pub mod ast {
+  include!(concat!(env!("OUT_DIR"), "/ast.rs"));
+}
+
+#[allow(missing_docs, clippy::pedantic)]
+pub mod parse {
+  include!(concat!(env!("OUT_DIR"), "/parse.rs"));
+
+  /// Written by hand, since `CallExpr` makes `Expr` left-recursive.
+  pub fn expr<E>(p: &mut Parser<'_, SK, E>)
+  where
+    E: Expected<SK>,
+  {
+    let en = p.enter();
+    let kind = if p.at(SK::Int) {
+      p.bump();
+      SK::IntExpr
+    } else if p.at(SK::Name) {
+      p.bump();
+      SK::NameExpr
+    } else if p.at(SK::LRound) {
+      p.bump();
+      expr(p);
+      p.eat(SK::RRound);
+      SK::ParenExpr
+    } else {
+      p.error_expected();
+      p.abandon(en);
+      return;
+    };
+    let mut ex = p.exit(en, kind);
+    while p.at(SK::LRound) {
+      let en = p.precede(ex);
+      p.bump();
+      while p.peek().is_some_and(|tok| tok.kind != SK::RRound) {
+        let en = p.enter();
+        expr(p);
+        if p.at(SK::Comma) {
+          p.bump();
+        }
+        p.exit(en, SK::Arg);
+      }
+      p.eat(SK::RRound);
+      ex = p.exit(en, SK::CallExpr);
+    }
+  }
+}
+
+#[allow(missing_docs, clippy::pedantic)]
+pub mod lex {
+  include!(concat!(env!("OUT_DIR"), "/lex.rs"));
+}
+
+#[allow(missing_docs, non_snake_case, clippy::pedantic)]
+pub mod make {
+  include!(concat!(env!("OUT_DIR"), "/make.rs"));
+}
+
+#[allow(missing_docs, clippy::pedantic)]
+pub mod visit {
+  include!(concat!(env!("OUT_DIR"), "/visit.rs"));
+}
+
+use kind::{SyntaxKind as SK, SyntaxNode};
+
+/// Lexes doc comments, which start with `///`, before the line comments would.
+fn doc_comment(s: &str) -> Option<token::lex::Lexed<SK>> {
+  if !s.starts_with("///") {
+    return None;
+  }
+  let len = s.find('\n').unwrap_or(s.len());
+  Some(token::lex::Lexed { kind: SK::DocComment, len, error: None })
+}
+
+/// Identifiers may also have primes, like `x'`.
+fn ident_continue(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+/// An error when parsing, with the kinds that were expected.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error(pub Vec<SK>);
+
+impl event_parse::Expected<SK> for Error {
+  fn expected(kind: SK) -> Self {
+    Error(vec![kind])
+  }
+
+  fn expected_one_of(kinds: &[SK]) -> Self {
+    Error(kinds.to_vec())
+  }
+}
+
+/// Parses the tokens into a tree.
+#[must_use]
+pub fn parse(
+  tokens: &[token::Token<'_, SK>],
+) -> (SyntaxNode, Vec<event_parse::rowan_sink::Error<SK, Error>>) {
+  let mut p = event_parse::Parser::new(tokens);
+  parse::root(&mut p);
+  let mut sink = event_parse::rowan_sink::RowanSink::default();
+  p.finish(&mut sink);
+  sink.finish()
+}
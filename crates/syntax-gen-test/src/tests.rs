@@ -0,0 +1,147 @@
+use crate::ast::{self, AstNode as _};
+use crate::kind::SyntaxKind as SK;
+use crate::visit::{self, Fold, Visitor};
+use crate::{Error, make, parse};
+
+fn lex(s: &str) -> Vec<token::Token<'_, SK>> {
+  let lex = crate::lex::lex(s);
+  assert!(lex.errors.is_empty(), "{:?}", lex.errors);
+  lex.tokens
+}
+
+#[test]
+fn lex_tokens() {
+  let got = crate::lex::lex("/// d\n// c\nlet x' = 12 #");
+  let tokens: Vec<_> = got.tokens.iter().map(|tok| (tok.kind, tok.text)).collect();
+  let want = [
+    (SK::DocComment, "/// d"),
+    (SK::Whitespace, "\n"),
+    (SK::LineComment, "// c"),
+    (SK::Whitespace, "\n"),
+    (SK::LetKw, "let"),
+    (SK::Whitespace, " "),
+    (SK::Name, "x'"),
+    (SK::Whitespace, " "),
+    (SK::Eq, "="),
+    (SK::Whitespace, " "),
+    (SK::Int, "12"),
+    (SK::Whitespace, " "),
+    (SK::Invalid, "#"),
+  ];
+  assert_eq!(tokens, want);
+  let error = token::lex::Error { range: 23..24, kind: token::lex::ErrorKind::InvalidSource };
+  assert_eq!(got.errors, [error]);
+}
+
+#[test]
+fn round_trip() {
+  let s = "let x = f(1, (y));\n// c\nfn g(a, b) { let z = 3; z }";
+  let (root, errors) = parse(&lex(s));
+  assert!(errors.is_empty(), "{errors:?}");
+  assert_eq!(root.to_string(), s);
+  let kinds: Vec<_> = root.children().map(|node| node.kind()).collect();
+  assert_eq!(kinds, [SK::LetItem, SK::FnItem]);
+}
+
+#[test]
+fn keyword_node() {
+  let block = make::block(&[], Some(&ast::Expr::IntExpr(make::int_expr("1"))));
+  let root = make::root(&[ast::Item::Try(make::r#try(&block))]);
+  let s = root.syntax().to_string();
+  assert_eq!(s, "try { 1 }");
+  let (reparsed, errors) = parse(&lex(&s));
+  assert!(errors.is_empty(), "{errors:?}");
+  let ast::Item::Try(got) = ast::Root::cast(reparsed).unwrap().items().next().unwrap() else {
+    panic!("not a try")
+  };
+  assert_eq!(got.block().unwrap().syntax().to_string(), "{ 1 }");
+}
+
+#[test]
+fn label_spelling() {
+  let (root, errors) = parse(&lex("fn g() { 1 }"));
+  assert!(errors.is_empty(), "{errors:?}");
+  let Some(ast::Item::FnItem(f)) = ast::Root::cast(root).unwrap().items().next() else {
+    panic!("not a fn")
+  };
+  let tail = f.block().unwrap().tailExpr().unwrap();
+  assert_eq!(tail.syntax().to_string(), "1");
+}
+
+#[test]
+fn syntax_error() {
+  let s = "let = ;";
+  let (root, errors) = parse(&lex(s));
+  assert_eq!(root.to_string(), s);
+  let got: Vec<_> = errors.into_iter().map(|e| (u32::from(e.range.start()), e.inner)).collect();
+  let want = [(4, Error(vec![SK::Name])), (6, Error(vec![SK::Int, SK::Name, SK::LRound]))];
+  assert_eq!(got, want);
+  // the `,` after a param is optional, but still expected.
+  let (_, errors) = parse(&lex("fn g(a 1) {}"));
+  assert_eq!(u32::from(errors[0].range.start()), 7);
+  assert_eq!(errors[0].inner, Error(vec![SK::Comma, SK::Name, SK::RRound]));
+}
+
+#[test]
+fn make_reparses() {
+  let one = make::int_expr("1");
+  let arg = make::arg(&ast::Expr::IntExpr(one), true);
+  let call = make::call_expr(&ast::Expr::NameExpr(make::name_expr("f")), &[arg]);
+  let item = make::let_item("y", &ast::Expr::CallExpr(call));
+  let params = make::params(&[make::param("a", true), make::param("b", false)]);
+  let block = make::block(&[], Some(&ast::Expr::NameExpr(make::name_expr("a"))));
+  let f = make::fn_item("g", Some(&params), &block);
+  let root = make::root(&[ast::Item::LetItem(item), ast::Item::FnItem(f)]);
+  let s = root.syntax().to_string();
+  assert_eq!(s, "let y = f(1,); fn g(a, b) { a }");
+  let (reparsed, errors) = parse(&lex(&s));
+  assert!(errors.is_empty(), "{errors:?}");
+  assert_eq!(format!("{reparsed:#?}"), format!("{:#?}", root.syntax()));
+}
+
+#[derive(Default)]
+struct Names {
+  names: Vec<String>,
+  exprs: usize,
+}
+
+impl Visitor for Names {
+  fn visit_name_expr(&mut self, node: &ast::NameExpr) {
+    self.names.push(node.syntax().to_string());
+  }
+
+  fn visit_expr(&mut self, node: &ast::Expr) {
+    self.exprs += 1;
+    visit::walk_expr(self, node);
+  }
+}
+
+#[test]
+fn visitor() {
+  let (root, _) = parse(&lex("let x = f(a, (b));\nfn g(c) { let z = 3; d }"));
+  let mut names = Names::default();
+  visit::walk(&mut names, &root);
+  assert_eq!(names.names, ["f", "a", "b", "d"]);
+  assert_eq!(names.exprs, 7);
+}
+
+/// Replaces every name expression with `0`.
+struct Zero;
+
+impl Fold for Zero {
+  fn fold_expr(&mut self, node: ast::Expr) -> ast::Expr {
+    match node {
+      ast::Expr::NameExpr(_) => ast::Expr::IntExpr(make::int_expr("0")),
+      node => visit::walk_fold_expr(self, node),
+    }
+  }
+}
+
+#[test]
+fn fold() {
+  let (root, _) = parse(&lex("let x = f(a, (b));\nfn g(c) { let z = 3; d }"));
+  let folded = visit::fold(&mut Zero, root.clone());
+  assert_eq!(folded.to_string(), "let x = 0(0, (0));\nfn g(c) { let z = 3; 0 }");
+  let root = ast::Root::cast(root).unwrap();
+  assert_eq!(Zero.fold_root(root).syntax().to_string(), folded.to_string());
+}
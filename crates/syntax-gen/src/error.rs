@@ -0,0 +1,169 @@
+//! Errors in the options passed to [`get`](crate::get).
+
+use std::fmt;
+
+/// All the errors in the options passed to [`get`](crate::get).
+///
+/// Both the `Display` and the `Debug` impls write one error per line, so build scripts can just
+/// `unwrap` the result of `get` to show them all.
+pub struct Errors(pub Vec<Error>);
+
+impl fmt::Display for Errors {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let n = self.0.len();
+    let s = if n == 1 { "" } else { "s" };
+    writeln!(f, "{n} error{s} in the syntax:")?;
+    for e in &self.0 {
+      writeln!(f, "- {e}")?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Debug for Errors {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Display::fmt(self, f)
+  }
+}
+
+impl std::error::Error for Errors {}
+
+/// An error in the options passed to [`get`](crate::get).
+#[derive(Debug)]
+pub struct Error {
+  /// Where the error is.
+  pub location: Location,
+  /// The kind of error.
+  pub kind: ErrorKind,
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.location, self.kind)
+  }
+}
+
+/// Where an error is.
+#[derive(Debug, Clone)]
+pub enum Location {
+  /// The grammar as a whole.
+  Grammar,
+  /// A node in the grammar.
+  Node {
+    /// The name of the node.
+    name: String,
+    /// The path to the rule in the node, as the index of the rule in each enclosing sequence or alt,
+    /// from the outside in. Empty if the rule is the whole node.
+    path: Vec<usize>,
+  },
+  /// A token in the grammar.
+  Token(String),
+  /// A field of the options.
+  Options(&'static str),
+}
+
+impl fmt::Display for Location {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Location::Grammar => f.write_str("grammar"),
+      Location::Node { name, path } => {
+        write!(f, "node `{name}`")?;
+        let mut sep = " at ";
+        for idx in path {
+          // 1-based, to match how people count rules.
+          write!(f, "{sep}{}", idx + 1)?;
+          sep = ".";
+        }
+        Ok(())
+      }
+      Location::Token(name) => write!(f, "token `{name}`"),
+      Location::Options(field) => write!(f, "option `{field}`"),
+    }
+  }
+}
+
+/// A kind of error.
+#[derive(Debug)]
+pub enum ErrorKind {
+  /// The grammar could not be parsed.
+  Parse(String),
+  /// The grammar has no nodes.
+  NoNodes,
+  /// An alt had both nodes and tokens.
+  MixedAlt,
+  /// An alt had something other than a node or token.
+  BadAltRule,
+  /// A field had more than one label.
+  TwoLabels(String, String),
+  /// A field was optional or repeated more than once.
+  NestedModifier,
+  /// A field was a sequence or alt.
+  BadFieldRule,
+  /// Punctuation had a char with no known name.
+  UnknownPunctuation(char),
+  /// A name is not an identifier, e.g. a `SyntaxKind` or the language.
+  InvalidName(String),
+  /// A name would be a function or parameter name that is a keyword which cannot be raw, like
+  /// `self`.
+  InvalidFnName(String),
+  /// More than one `SyntaxKind` would have the name.
+  DuplicateName(String),
+  /// There is no such token in the grammar.
+  NoSuchToken(String),
+  /// There is no such node in the grammar.
+  NoSuchNode(String),
+  /// There is no such `SyntaxKind`.
+  NoSuchKind(String),
+  /// The name is not in the trivia.
+  NotTrivia(String),
+  /// The string is not a path.
+  InvalidPath(String),
+  /// A node that is not overridden in the parser options is left-recursive.
+  LeftRecursive,
+  /// An alternative may start with the same tokens as an earlier one, given by its index, so the
+  /// generated parser never picks it for those tokens.
+  OverlappingAlt(usize, Vec<String>),
+  /// An alternative may be empty, but the generated parser reports an error when no alternative
+  /// starts with the current token.
+  NullableAlt,
+}
+
+impl fmt::Display for ErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ErrorKind::Parse(e) => write!(f, "couldn't parse ungrammar: {e}"),
+      ErrorKind::NoNodes => f.write_str("there are no nodes"),
+      ErrorKind::MixedAlt => f.write_str("an alt must be all nodes or all tokens"),
+      ErrorKind::BadAltRule => f.write_str("each alternative must be a node or a token"),
+      ErrorKind::TwoLabels(old, new) => {
+        write!(f, "already have label `{old}`, cannot have new label `{new}`")
+      }
+      ErrorKind::NestedModifier => f.write_str("cannot be optional or repeated more than once"),
+      ErrorKind::BadFieldRule => {
+        f.write_str("each field must be a node or a token, not a nested sequence or alt")
+      }
+      ErrorKind::UnknownPunctuation(c) => write!(f, "don't know the name for `{c}`"),
+      ErrorKind::InvalidName(name) => write!(f, "`{name}` is not a valid identifier"),
+      ErrorKind::InvalidFnName(name) => {
+        write!(f, "`{name}` cannot be the name of a function or parameter")
+      }
+      ErrorKind::DuplicateName(name) => write!(f, "more than one SyntaxKind is named `{name}`"),
+      ErrorKind::NoSuchToken(name) => write!(f, "no token `{name}` in the grammar"),
+      ErrorKind::NoSuchNode(name) => write!(f, "no node `{name}` in the grammar"),
+      ErrorKind::NoSuchKind(name) => write!(f, "no SyntaxKind `{name}`"),
+      ErrorKind::NotTrivia(name) => write!(f, "`{name}` is not trivia"),
+      ErrorKind::InvalidPath(path) => write!(f, "`{path}` is not a path"),
+      ErrorKind::LeftRecursive => f.write_str("left-recursive, so it must be overridden"),
+      ErrorKind::OverlappingAlt(idx, tokens) => {
+        write!(f, "may start with the same tokens as alternative {}:", idx + 1)?;
+        let mut sep = " ";
+        for tok in tokens {
+          write!(f, "{sep}`{tok}`")?;
+          sep = ", ";
+        }
+        Ok(())
+      }
+      ErrorKind::NullableAlt => f.write_str("an alternative may not be empty"),
+    }
+  }
+}
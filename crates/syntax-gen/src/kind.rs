@@ -1,11 +1,21 @@
 use crate::util::Cx;
+use proc_macro2::Ident;
 use quote::quote;
 use std::cmp::Reverse;
 
+/// The trivia `SyntaxKind`s.
+pub(crate) struct Trivia {
+  pub(crate) all: Vec<Ident>,
+  /// The ones which are comments.
+  pub(crate) comments: Vec<Ident>,
+  /// The ones which are doc comments.
+  pub(crate) doc_comments: Vec<Ident>,
+}
+
 #[allow(clippy::too_many_lines)]
 pub(crate) fn get(
   Cx { grammar, tokens, lang, .. }: Cx,
-  trivia: &[proc_macro2::Ident],
+  trivia: &Trivia,
   node_syntax_kinds: Vec<proc_macro2::Ident>,
   file: &str,
 ) -> proc_macro2::TokenStream {
@@ -64,11 +74,22 @@ pub(crate) fn get(
         Some(quote! { Self::#kind => #doc })
       },
     );
-  let self_trivia = trivia.iter().map(|id| {
+  let self_trivia = trivia.all.iter().map(|id| {
     quote! { Self::#id }
   });
+  let trivia_class_arms = trivia.all.iter().map(|id| {
+    let class = if trivia.doc_comments.contains(id) {
+      quote! { DocComment }
+    } else if trivia.comments.contains(id) {
+      quote! { Comment }
+    } else {
+      quote! { Whitespace }
+    };
+    quote! { Self::#id => Some(token::TriviaClass::#class) }
+  });
   // the order is intentional
   let syntax_kinds: Vec<_> = trivia
+    .all
     .iter()
     .cloned()
     .chain(keywords.iter().chain(punctuation.iter()).map(|(_, tok)| tok.name_ident()))
@@ -121,6 +142,13 @@ pub(crate) fn get(
       fn is_trivia(&self) -> bool {
         matches!(*self, #(#self_trivia)|*)
       }
+
+      fn trivia_class(&self) -> Option<token::TriviaClass> {
+        match *self {
+          #(#trivia_class_arms ,)*
+          _ => None,
+        }
+      }
     }
 
     impl fmt::Display for SyntaxKind {
@@ -0,0 +1,65 @@
+use crate::LexOptions;
+use crate::token::ident;
+use proc_macro2::{Literal, TokenStream};
+use quote::{ToTokens as _, quote};
+
+pub(crate) fn get(opts: &LexOptions<'_>, file: &str) -> TokenStream {
+  let whitespace = ident(opts.whitespace);
+  let ident_kind = ident(opts.ident);
+  let error = ident(opts.error);
+  let hooks = opts.hooks.iter().map(|&hook| path(hook));
+  let line_comment = option(opts.line_comment.map(|(start, kind)| {
+    let kind = ident(kind);
+    quote! { (#start, SK::#kind) }
+  }));
+  let block_comment = option(opts.block_comment.as_ref().map(|bc| {
+    let (open, close, nested, kind) = (bc.open, bc.close, bc.nested, ident(bc.kind));
+    quote! { token::lex::BlockComment { open: #open, close: #close, nested: #nested, kind: SK::#kind } }
+  }));
+  let string = option(opts.string.as_ref().map(|q| {
+    let quote_char = Literal::character(q.quote);
+    let escape = option(q.escape.map(|c| Literal::character(c).into_token_stream()));
+    let kind = ident(q.kind);
+    quote! { token::lex::Quoted { quote: #quote_char, escape: #escape, kind: SK::#kind } }
+  }));
+  let number = option(opts.number.map(|kind| {
+    let kind = ident(kind);
+    quote! { SK::#kind }
+  }));
+  let ident_start =
+    opts.ident_start.map_or_else(|| quote! { |c| c.is_alphabetic() || c == '_' }, path);
+  let ident_continue =
+    opts.ident_continue.map_or_else(|| quote! { |c| c.is_alphanumeric() || c == '_' }, path);
+  quote! {
+    use crate::kind::SyntaxKind as SK;
+
+    pub const GENERATED_BY: &str = #file;
+
+    pub const CONFIG: token::lex::Config<'static, SK> = token::lex::Config {
+      hooks: &[#(#hooks),*],
+      whitespace: SK::#whitespace,
+      line_comment: #line_comment,
+      block_comment: #block_comment,
+      string: #string,
+      number: #number,
+      ident_start: #ident_start,
+      ident_continue: #ident_continue,
+      keyword: SK::keyword,
+      ident: SK::#ident_kind,
+      punctuation: &SK::PUNCTUATION,
+      error: SK::#error,
+    };
+
+    pub fn lex(s: &str) -> token::lex::Lex<'_, SK> {
+      token::lex::get(s, &CONFIG)
+    }
+  }
+}
+
+fn path(s: &str) -> TokenStream {
+  s.parse().unwrap_or_else(|e| panic!("{s} is not a path: {e}"))
+}
+
+fn option(ts: Option<TokenStream>) -> TokenStream {
+  ts.map_or_else(|| quote! { None }, |ts| quote! { Some(#ts) })
+}
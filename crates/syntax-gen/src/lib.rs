@@ -4,16 +4,25 @@
 
 mod alt;
 mod ast;
+mod error;
 mod kind;
+mod lex;
+mod make;
+mod parse;
 mod seq;
+#[cfg(test)]
+mod tests;
 mod token;
 mod util;
+mod validate;
+mod visit;
 
 use crate::util::Cx;
 use fast_hash::FxHashSet;
 use std::{collections::HashMap, hash::BuildHasher};
 use ungrammar::{Grammar, Rule};
 
+pub use error::{Error, ErrorKind, Errors, Location};
 pub use token::{Kind as TokenKind, Token};
 
 /// The options to pass to `gen`.
@@ -23,6 +32,10 @@ pub struct Options<'a, S> {
   pub lang: &'a str,
   /// A list of all the `SyntaxKind`s which should be made as trivia.
   pub trivia: &'a [&'a str],
+  /// The trivia `SyntaxKind`s which are comments. The rest of the trivia is whitespace.
+  pub comments: &'a [&'a str],
+  /// The trivia `SyntaxKind`s which are doc comments.
+  pub doc_comments: &'a [&'a str],
   /// Text of the ungrammar for the language, possibly via `include_str!`.
   pub grammar: &'a str,
   /// A map from token names to documentation.
@@ -33,29 +46,206 @@ pub struct Options<'a, S> {
   ///
   /// Will be added as doc in the generated files.
   pub file: &'a str,
+  /// If this is `Some`, also generate a parser.
+  pub parser: Option<ParserOptions<'a>>,
+  /// If this is `Some`, also generate a lexer.
+  pub lexer: Option<LexOptions<'a>>,
+  /// If this is `Some`, also generate constructors for nodes.
+  pub make: Option<MakeOptions<'a>>,
+  /// If this is `Some`, also generate a visitor, and maybe a fold.
+  pub visit: Option<VisitOptions>,
 }
 
-/// Generates Rust code from the `grammar` of the `lang` and writes it to two files:
+impl<'a, S> Options<'a, S> {
+  /// Returns options with the given required fields, and no comments, doc comments, or optional
+  /// outputs. Set the other fields with struct update syntax, e.g.
+  /// `Options { parser: Some(..), ..Options::new(..) }`.
+  #[must_use]
+  pub fn new(
+    lang: &'a str,
+    trivia: &'a [&'a str],
+    grammar: &'a str,
+    doc: &'a HashMap<&'a str, &'a str, S>,
+    special: &'a HashMap<&'a str, &'a str, S>,
+    file: &'a str,
+  ) -> Self {
+    Self {
+      lang,
+      trivia,
+      comments: &[],
+      doc_comments: &[],
+      grammar,
+      doc,
+      special,
+      file,
+      parser: None,
+      lexer: None,
+      make: None,
+      visit: None,
+    }
+  }
+}
+
+/// The options for generating a parser.
+///
+/// The parser has a recursive-descent function for each node, which takes an
+/// `&mut event_parse::Parser<'_, SyntaxKind, E>` where `E: event_parse::Expected<SyntaxKind>`.
+/// Where the grammar allows more than one thing, the function decides what to parse by looking at
+/// the current token, so the alternatives of each alt must start with different tokens, and none of
+/// them may be empty.
+#[derive(Debug)]
+pub struct ParserOptions<'a> {
+  /// The nodes whose functions will be written by hand instead of generated, e.g. because they are
+  /// left-recursive, like many kinds of expressions, or they need more than one token to decide
+  /// what to parse.
+  ///
+  /// The generated functions call these by name, so they must be defined in the same module as the
+  /// generated ones, with the same signature, except that the return type may differ.
+  pub overrides: &'a [&'a str],
+}
+
+/// The options for generating a lexer.
+///
+/// The lexer uses `token::lex`, with the keywords and punctuation from the grammar. Kinds are given
+/// by the names of `SyntaxKind`s, which must be trivia or special tokens. Functions are given by
+/// paths, which must be valid in the module the generated lexer is in.
+#[derive(Debug)]
+pub struct LexOptions<'a> {
+  /// The kind for whitespace.
+  pub whitespace: &'a str,
+  /// The start of a line comment, and the kind for line comments.
+  pub line_comment: Option<(&'a str, &'a str)>,
+  /// Block comments.
+  pub block_comment: Option<LexBlockComment<'a>>,
+  /// String literals.
+  pub string: Option<LexString<'a>>,
+  /// The kind for number literals.
+  pub number: Option<&'a str>,
+  /// The kind for identifiers.
+  pub ident: &'a str,
+  /// A `fn(char) -> bool` for whether a char may start an identifier. Defaults to alphabetic chars
+  /// and `_`.
+  pub ident_start: Option<&'a str>,
+  /// A `fn(char) -> bool` for whether a char may continue an identifier. Defaults to alphanumeric
+  /// chars and `_`.
+  pub ident_continue: Option<&'a str>,
+  /// Functions `fn(&str) -> Option<token::lex::Lexed<SyntaxKind>>` to try before everything else,
+  /// for other tokens.
+  pub hooks: &'a [&'a str],
+  /// The kind for a char which starts no token.
+  pub error: &'a str,
+}
+
+/// Block comments for a generated lexer.
+#[derive(Debug)]
+pub struct LexBlockComment<'a> {
+  /// The start of a block comment.
+  pub open: &'a str,
+  /// The end of a block comment.
+  pub close: &'a str,
+  /// Whether block comments nest.
+  pub nested: bool,
+  /// The kind.
+  pub kind: &'a str,
+}
+
+/// String literals for a generated lexer.
+#[derive(Debug)]
+pub struct LexString<'a> {
+  /// The char which starts and ends the literal.
+  pub quote: char,
+  /// The char which escapes the char after it.
+  pub escape: Option<char>,
+  /// The kind.
+  pub kind: &'a str,
+}
+
+/// The options for generating constructors for nodes.
+///
+/// There is a constructor function for each node that is not an alt, which takes the children of the
+/// node and returns a new node built with a `rowan::GreenNodeBuilder`. Children which are nodes are
+/// taken as references to the AST types, and children which are special tokens are taken as `&str`.
+/// Punctuation and keywords are added automatically, so they are taken only as a `bool` if optional
+/// or a `usize` if repeated.
 ///
-/// - `$OUT_DIR/kind.rs`, which will contain definitions for the language's `SyntaxKind` and
-///   associated types, using all the different tokens extracted from `grammar`.
-/// - `$OUT_DIR/ast.rs`, which will contain a strongly-typed API for traversing an abstract syntax
-///   tree, based on the `grammar`.
+/// The children are separated by whitespace, except around the given punctuation and keywords.
+#[derive(Debug)]
+pub struct MakeOptions<'a> {
+  /// The kind for whitespace.
+  pub whitespace: &'a str,
+  /// The text of the whitespace to put between children, e.g. `" "`.
+  pub space: &'a str,
+  /// The punctuation and keywords, as written in the grammar, to not put whitespace before, e.g.
+  /// `";"`.
+  pub no_space_before: &'a [&'a str],
+  /// The punctuation and keywords, as written in the grammar, to not put whitespace after, e.g.
+  /// `"("`.
+  pub no_space_after: &'a [&'a str],
+}
+
+/// The options for generating a visitor over the AST.
+///
+/// The `Visitor` trait has a method for every node that is not a token alt, which by default visits
+/// the children of the node. The `Fold` trait is like it, but each method takes a node and returns a
+/// node to replace it, which by default is the node with its children folded.
+#[derive(Debug)]
+pub struct VisitOptions {
+  /// Whether to also generate a `Fold`.
+  pub fold: bool,
+}
+
+/// Generates Rust code from the `grammar` of the `lang` and writes it to files in `$OUT_DIR`.
+///
+/// It always writes:
+///
+/// - `kind.rs`, which will contain definitions for the language's `SyntaxKind` and associated
+///   types, using all the different tokens extracted from `grammar`.
+/// - `ast.rs`, which will contain a strongly-typed API for traversing an abstract syntax tree,
+///   based on the `grammar`.
+///
+/// Depending on the options, it also writes:
+///
+/// - `parse.rs`, with [`ParserOptions`], which will contain a parser, based on the `grammar`.
+/// - `lex.rs`, with [`LexOptions`], which will contain a lexer.
+/// - `make.rs`, with [`MakeOptions`], which will contain constructors for nodes.
+/// - `visit.rs`, with [`VisitOptions`], which will contain a `Visitor` trait, and maybe a `Fold`
+///   trait, with a method for every node that defaults to walking its children.
+///
+/// The optional files expect `kind.rs` to be included in `crate::kind`, and `make.rs` and
+/// `visit.rs` also expect `ast.rs` to be included in `crate::ast`.
 ///
 /// The generated Rust files will depend on:
 ///
 /// - `rowan` from crates.io
 /// - `token` from language-util
+/// - `event-parse` from language-util, for the parser
+///
+/// # Errors
+///
+/// If the options are invalid, e.g. if the grammar has a rule we cannot generate code for, or if a
+/// node that is not overridden in the [`ParserOptions`] is left-recursive or has an alt that the
+/// parser cannot decide by looking at one token. This checks everything before generating any
+/// code, and returns all the errors.
 ///
 /// # Panics
 ///
-/// If this process failed.
-pub fn get<S>(opts: &Options<'_, S>)
+/// If writing the files failed.
+pub fn get<S>(opts: &Options<'_, S>) -> Result<(), Errors>
 where
   S: BuildHasher,
 {
+  let grammar: Grammar = match opts.grammar.parse() {
+    Ok(x) => x,
+    Err(e) => {
+      let kind = ErrorKind::Parse(e.to_string());
+      return Err(Errors(vec![Error { location: Location::Grammar, kind }]));
+    }
+  };
+  let errors = validate::get(opts, &grammar);
+  if !errors.is_empty() {
+    return Err(Errors(errors));
+  }
   let lang = token::ident(opts.lang);
-  let grammar: Grammar = opts.grammar.parse().expect("couldn't parse ungrammar");
   let tokens = token::TokenDb::new(&grammar, opts.doc, opts.special);
   let mut types = Vec::<proc_macro2::TokenStream>::new();
   let mut node_syntax_kinds = Vec::<proc_macro2::Ident>::new();
@@ -87,7 +277,25 @@ where
   }
   let ast_rs = ast::get(&cx.lang, &types, opts.file);
   write_rs_tokens::go(ast_rs, "ast.rs");
-  let trivia: Vec<_> = opts.trivia.iter().map(|&x| token::ident(x)).collect();
+  if let Some(parser) = &opts.parser {
+    let parse_rs = parse::get(&cx, parser.overrides, opts.file);
+    write_rs_tokens::go(parse_rs, "parse.rs");
+  }
+  if let Some(lexer) = &opts.lexer {
+    write_rs_tokens::go(lex::get(lexer, opts.file), "lex.rs");
+  }
+  if let Some(make) = &opts.make {
+    write_rs_tokens::go(make::get(&cx, make, opts.file), "make.rs");
+  }
+  if let Some(visit) = &opts.visit {
+    write_rs_tokens::go(visit::get(&cx, visit, opts.file), "visit.rs");
+  }
+  let trivia = kind::Trivia {
+    all: opts.trivia.iter().map(|&x| token::ident(x)).collect(),
+    comments: opts.comments.iter().map(|&x| token::ident(x)).collect(),
+    doc_comments: opts.doc_comments.iter().map(|&x| token::ident(x)).collect(),
+  };
   let kind_rs = kind::get(cx, &trivia, node_syntax_kinds, opts.file);
   write_rs_tokens::go(kind_rs, "kind.rs");
+  Ok(())
 }
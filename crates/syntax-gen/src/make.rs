@@ -0,0 +1,222 @@
+use crate::MakeOptions;
+use crate::token::ident;
+use crate::util::{Cx, fn_name, label_name, unwrap_token};
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use ungrammar::{Node, Rule};
+
+pub(crate) fn get(cx: &Cx, opts: &MakeOptions<'_>, file: &str) -> TokenStream {
+  let lang = &cx.lang;
+  let whitespace = ident(opts.whitespace);
+  let space = opts.space;
+  let no_space_before = opts.no_space_before;
+  let no_space_after = opts.no_space_after;
+  let fns = cx.grammar.iter().filter_map(|node| {
+    let rules = match &cx.grammar[node].rule {
+      Rule::Alt(_) => return None,
+      Rule::Seq(rules) => rules.as_slice(),
+      rule => std::slice::from_ref(rule),
+    };
+    Some(make_fn(cx, node, rules))
+  });
+  quote! {
+    use crate::ast::{self, AstNode as _};
+    use crate::kind::{SyntaxKind as SK, SyntaxNode, #lang};
+
+    pub const GENERATED_BY: &str = #file;
+
+    const SPACE: &str = #space;
+    const NO_SPACE_BEFORE: &[&str] = &[#(#no_space_before),*];
+    const NO_SPACE_AFTER: &[&str] = &[#(#no_space_after),*];
+
+    struct Builder {
+      inner: rowan::GreenNodeBuilder<'static>,
+      /// Whether the next child should not have a space before it.
+      glue: bool,
+    }
+
+    impl Builder {
+      fn new(kind: SK) -> Self {
+        let mut inner = rowan::GreenNodeBuilder::new();
+        inner.start_node(kind.into());
+        Self { inner, glue: true }
+      }
+
+      fn space(&mut self, glue: bool) {
+        if !self.glue && !glue && !SPACE.is_empty() {
+          self.inner.token(SK::#whitespace.into(), SPACE);
+        }
+      }
+
+      fn token(&mut self, kind: SK, text: &str) {
+        self.space(NO_SPACE_BEFORE.contains(&text));
+        self.inner.token(kind.into(), text);
+        self.glue = NO_SPACE_AFTER.contains(&text);
+      }
+
+      fn node(&mut self, node: &SyntaxNode) {
+        self.space(false);
+        for event in node.preorder_with_tokens() {
+          match event {
+            rowan::WalkEvent::Enter(rowan::NodeOrToken::Node(node)) => {
+              self.inner.start_node(node.kind().into());
+            }
+            rowan::WalkEvent::Enter(rowan::NodeOrToken::Token(token)) => {
+              self.inner.token(token.kind().into(), token.text());
+            }
+            rowan::WalkEvent::Leave(rowan::NodeOrToken::Node(_)) => self.inner.finish_node(),
+            rowan::WalkEvent::Leave(rowan::NodeOrToken::Token(_)) => {}
+          }
+        }
+        self.glue = false;
+      }
+
+      fn finish<N>(mut self) -> N
+      where
+        N: ast::AstNode<Language = #lang>,
+      {
+        self.inner.finish_node();
+        N::cast(SyntaxNode::new_root(self.inner.finish())).expect("built the wrong kind of node")
+      }
+    }
+
+    #(#fns)*
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Modifier {
+  Regular,
+  Repeated,
+  Optional,
+}
+
+fn make_fn(cx: &Cx, node: Node, rules: &[Rule]) -> TokenStream {
+  let name = ident(&cx.grammar[node].name);
+  let fn_name = fn_name(&cx.grammar[node].name);
+  let mut params = Vec::<TokenStream>::new();
+  let mut stmts = Vec::<TokenStream>::new();
+  for rule in rules {
+    child(cx, rule, &mut params, &mut stmts);
+  }
+  quote! {
+    pub fn #fn_name(#(#params),*) -> ast::#name {
+      let mut b = Builder::new(SK::#name);
+      #(#stmts)*
+      b.finish()
+    }
+  }
+}
+
+/// Pushes the parameters for the child, if any, and the statements to add it to the builder `b`.
+fn child(cx: &Cx, mut rule: &Rule, params: &mut Vec<TokenStream>, stmts: &mut Vec<TokenStream>) {
+  let mut modifier = Modifier::Regular;
+  let mut label: Option<&str> = None;
+  loop {
+    match rule {
+      Rule::Labeled { label: l, rule: r } => {
+        label = Some(l.as_str());
+        rule = r.as_ref();
+      }
+      Rule::Opt(r) => {
+        modifier = Modifier::Optional;
+        rule = r.as_ref();
+      }
+      Rule::Rep(r) => {
+        modifier = Modifier::Repeated;
+        rule = r.as_ref();
+      }
+      Rule::Node(_) | Rule::Token(_) => break,
+      Rule::Seq(_) | Rule::Alt(_) => panic!("bad field rule: {rule:?}"),
+    }
+  }
+  let param_name = |name: &str| -> Ident {
+    match (label, modifier) {
+      (Some(label), _) => label_name(label),
+      (None, Modifier::Repeated) => fn_name(&format!("{name}s")),
+      (None, Modifier::Optional | Modifier::Regular) => fn_name(name),
+    }
+  };
+  match rule {
+    Rule::Node(node) => {
+      let name = cx.grammar[*node].name.as_str();
+      let ty = ident(name);
+      let param = param_name(name);
+      if cx.token_alts.contains(&ty) {
+        let kind_ty = quote::format_ident!("{name}Kind");
+        let Rule::Alt(rules) = &cx.grammar[*node].rule else {
+          unreachable!("token alt is not an alt")
+        };
+        let arms = rules.iter().map(|rule| {
+          let kind = cx.tokens.get(unwrap_token(rule)).name_ident();
+          quote! { ast::#kind_ty::#kind => SK::#kind }
+        });
+        let add = quote! {
+          let kind = match x { #(#arms ,)* };
+          b.token(kind, x.to_str());
+        };
+        let tys = (quote! { ast::#kind_ty }, quote! { ast::#kind_ty });
+        push(modifier, &param, &tys, &add, params, stmts);
+      } else {
+        let add = quote! { b.node(x.syntax()); };
+        let tys = (quote! { &ast::#ty }, quote! { ast::#ty });
+        push(modifier, &param, &tys, &add, params, stmts);
+      }
+    }
+    Rule::Token(tok) => {
+      let token = cx.tokens.get(*tok);
+      let kind = token.name_ident();
+      if cx.tokens.special.contains_key(tok) {
+        let param = param_name(&token.name);
+        let add = quote! { b.token(SK::#kind, x); };
+        let tys = (quote! { &str }, quote! { &str });
+        push(modifier, &param, &tys, &add, params, stmts);
+      } else {
+        // the text of punctuation and keywords is fixed, so we only need to know whether to add
+        // them if they are optional, or how many if they are repeated.
+        let text = cx.grammar[*tok].name.as_str();
+        let add = quote! { b.token(SK::#kind, #text); };
+        match modifier {
+          Modifier::Regular => stmts.push(add),
+          Modifier::Optional => {
+            let param = param_name(&token.name);
+            params.push(quote! { #param: bool });
+            stmts.push(quote! { if #param { #add } });
+          }
+          Modifier::Repeated => {
+            let param = param_name(&token.name);
+            params.push(quote! { #param: usize });
+            stmts.push(quote! { for _ in 0..#param { #add } });
+          }
+        }
+      }
+    }
+    _ => unreachable!("not a node or token"),
+  }
+}
+
+/// Pushes the parameter and statements for a child, which is passed as `single` if it is regular or
+/// optional, and as a slice of `elem` if it is repeated. `add` adds a child `x` of either type.
+fn push(
+  modifier: Modifier,
+  param: &Ident,
+  (single, elem): &(TokenStream, TokenStream),
+  add: &TokenStream,
+  params: &mut Vec<TokenStream>,
+  stmts: &mut Vec<TokenStream>,
+) {
+  match modifier {
+    Modifier::Regular => {
+      params.push(quote! { #param: #single });
+      stmts.push(quote! { let x = #param; #add });
+    }
+    Modifier::Optional => {
+      params.push(quote! { #param: Option<#single> });
+      stmts.push(quote! { if let Some(x) = #param { #add } });
+    }
+    Modifier::Repeated => {
+      params.push(quote! { #param: &[#elem] });
+      stmts.push(quote! { for x in #param { #add } });
+    }
+  }
+}
@@ -0,0 +1,254 @@
+use crate::token::ident;
+use crate::util::{Cx, fn_name, node_named};
+use fast_hash::{FxHashMap, FxHashSet};
+use proc_macro2::TokenStream;
+use quote::quote;
+use ungrammar::{Grammar, Node, Rule, Token};
+
+/// The tokens a rule may start with, and whether it may be empty.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct First {
+  pub(crate) tokens: FxHashSet<Token>,
+  pub(crate) nullable: bool,
+}
+
+/// The first tokens of every node.
+pub(crate) struct FirstSets<'a> {
+  grammar: &'a Grammar,
+  first: FxHashMap<Node, First>,
+}
+
+impl<'a> FirstSets<'a> {
+  /// Computes the first tokens of every node, iterating until nothing changes, since rules may be
+  /// recursive.
+  pub(crate) fn new(grammar: &'a Grammar) -> Self {
+    let mut ret = Self { grammar, first: FxHashMap::default() };
+    loop {
+      let mut changed = false;
+      for node in grammar.iter() {
+        let first = ret.get(&grammar[node].rule);
+        if ret.first.get(&node) != Some(&first) {
+          ret.first.insert(node, first);
+          changed = true;
+        }
+      }
+      if !changed {
+        break;
+      }
+    }
+    ret
+  }
+
+  pub(crate) fn get(&self, rule: &Rule) -> First {
+    match rule {
+      Rule::Labeled { rule, .. } => self.get(rule),
+      Rule::Node(node) => self.first.get(node).cloned().unwrap_or_default(),
+      Rule::Token(tok) => First { tokens: FxHashSet::from_iter([*tok]), nullable: false },
+      Rule::Seq(rules) => {
+        let mut ret = First { tokens: FxHashSet::default(), nullable: true };
+        for rule in rules {
+          let first = self.get(rule);
+          ret.tokens.extend(first.tokens);
+          if !first.nullable {
+            ret.nullable = false;
+            break;
+          }
+        }
+        ret
+      }
+      Rule::Alt(rules) => {
+        let mut ret = First::default();
+        for rule in rules {
+          let first = self.get(rule);
+          ret.tokens.extend(first.tokens);
+          ret.nullable |= first.nullable;
+        }
+        ret
+      }
+      Rule::Opt(rule) | Rule::Rep(rule) => First { nullable: true, ..self.get(rule) },
+    }
+  }
+
+  /// Returns the nodes that are not overridden but may start with themselves, since the generated
+  /// functions for them would recurse forever.
+  pub(crate) fn left_recursive(&self, overrides: &FxHashSet<Node>) -> Vec<Node> {
+    self
+      .grammar
+      .iter()
+      .filter(|&node| {
+        let mut stack = vec![node];
+        let mut seen = FxHashSet::default();
+        while let Some(cur) = stack.pop() {
+          if overrides.contains(&cur) || !seen.insert(cur) {
+            continue;
+          }
+          let mut left = Vec::new();
+          self.left(&self.grammar[cur].rule, &mut left);
+          if left.contains(&node) {
+            return true;
+          }
+          stack.extend(left);
+        }
+        false
+      })
+      .collect()
+  }
+
+  /// Pushes the nodes the rule may start with onto `out`.
+  fn left(&self, rule: &Rule, out: &mut Vec<Node>) {
+    match rule {
+      Rule::Labeled { rule, .. } | Rule::Opt(rule) | Rule::Rep(rule) => self.left(rule, out),
+      Rule::Node(node) => out.push(*node),
+      Rule::Token(_) => {}
+      Rule::Seq(rules) => {
+        for rule in rules {
+          self.left(rule, out);
+          if !self.get(rule).nullable {
+            break;
+          }
+        }
+      }
+      Rule::Alt(rules) => {
+        for rule in rules {
+          self.left(rule, out);
+        }
+      }
+    }
+  }
+}
+
+struct St<'a> {
+  cx: &'a Cx,
+  first: FirstSets<'a>,
+}
+
+pub(crate) fn get(cx: &Cx, overrides: &[&str], file: &str) -> TokenStream {
+  let overrides: FxHashSet<_> =
+    overrides.iter().filter_map(|&name| node_named(&cx.grammar, name)).collect();
+  let st = St { cx, first: FirstSets::new(&cx.grammar) };
+  let fns = cx.grammar.iter().filter(|node| !overrides.contains(node)).map(|node| st.node(node));
+  quote! {
+    use crate::kind::SyntaxKind as SK;
+    use event_parse::{Expected, Parser};
+
+    pub const GENERATED_BY: &str = #file;
+
+    #[allow(unused)]
+    fn at_any<E>(p: &mut Parser<'_, SK, E>, kinds: &[SK]) -> bool
+    where
+      E: Expected<SK>,
+    {
+      kinds.iter().any(|&kind| p.at(kind))
+    }
+
+    #[allow(unused)]
+    fn error_expected_any<E>(p: &mut Parser<'_, SK, E>, kinds: &[SK])
+    where
+      E: Expected<SK>,
+    {
+      for &kind in kinds {
+        p.at(kind);
+      }
+      p.error_expected();
+    }
+
+    #(#fns)*
+  }
+}
+
+impl St<'_> {
+  fn node(&self, node: Node) -> TokenStream {
+    let data = &self.cx.grammar[node];
+    let name = ident(&data.name);
+    let fn_name = fn_name(&data.name);
+    let body = match &data.rule {
+      // alts of nodes have no node of their own, and alts of tokens are just tokens.
+      rule @ Rule::Alt(_) => self.rule(rule),
+      rule => {
+        let body = self.rule(rule);
+        quote! {
+          let en = p.enter();
+          #body
+          p.exit(en, SK::#name);
+        }
+      }
+    };
+    quote! {
+      pub fn #fn_name<E>(p: &mut Parser<'_, SK, E>)
+      where
+        E: Expected<SK>,
+      {
+        #body
+      }
+    }
+  }
+
+  fn rule(&self, rule: &Rule) -> TokenStream {
+    match rule {
+      Rule::Labeled { rule, .. } => self.rule(rule),
+      Rule::Node(node) => {
+        let fn_name = fn_name(&self.cx.grammar[*node].name);
+        quote! { #fn_name(p); }
+      }
+      Rule::Token(tok) => {
+        let kind = self.cx.tokens.get(*tok).name_ident();
+        quote! { p.eat(SK::#kind); }
+      }
+      Rule::Seq(rules) => {
+        let rules = rules.iter().map(|rule| self.rule(rule));
+        quote! { #(#rules)* }
+      }
+      Rule::Alt(rules) => {
+        let arms = rules.iter().map(|rule| {
+          let kinds = self.kinds(rule);
+          let body = self.guarded(rule);
+          quote! { if at_any(p, &[#(#kinds),*]) { #body } }
+        });
+        let kinds = self.kinds(rule);
+        quote! {
+          #(#arms else)* {
+            error_expected_any(p, &[#(#kinds),*]);
+          }
+        }
+      }
+      Rule::Opt(inner) => {
+        let kinds = self.kinds(inner);
+        let body = self.guarded(inner);
+        quote! { if at_any(p, &[#(#kinds),*]) { #body } }
+      }
+      Rule::Rep(inner) => {
+        let kinds = self.kinds(inner);
+        let body = self.guarded(inner);
+        quote! { while at_any(p, &[#(#kinds),*]) { #body } }
+      }
+    }
+  }
+
+  /// Like `rule`, but for when we already know the current token is one of the first tokens of the
+  /// rule.
+  fn guarded(&self, rule: &Rule) -> TokenStream {
+    match rule {
+      Rule::Token(_) => quote! { p.bump(); },
+      rule => self.rule(rule),
+    }
+  }
+
+  /// Returns the kinds of the first tokens of the rule, in a stable order.
+  fn kinds(&self, rule: &Rule) -> Vec<TokenStream> {
+    let mut names: Vec<_> = self
+      .first
+      .get(rule)
+      .tokens
+      .into_iter()
+      .map(|tok| self.cx.tokens.get(tok).name.as_str())
+      .collect();
+    names.sort_unstable();
+    names
+      .into_iter()
+      .map(|name| {
+        let name = ident(name);
+        quote! { SK::#name }
+      })
+      .collect()
+  }
+}
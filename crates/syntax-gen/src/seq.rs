@@ -1,8 +1,7 @@
 use crate::token::ident;
-use crate::util::Cx;
-use identifier_case::pascal_to_snake;
+use crate::util::{Cx, fn_name, label_name};
 use proc_macro2::{Ident, TokenStream};
-use quote::{format_ident, quote};
+use quote::quote;
 use std::hash::Hash;
 use ungrammar::Rule;
 
@@ -117,16 +116,10 @@ fn field<'cx>(cx: &'cx Cx, counts: &mut Counts<&'cx str>, mut rule: &Rule) -> To
       Rule::Seq(_) | Rule::Alt(_) => panic!("bad field rule: {rule:?}"),
     }
   }
-  #[allow(clippy::single_match_else)]
-  let field_name = match label {
-    Some(x) => ident(x),
-    None => {
-      let to_snake = pascal_to_snake(name);
-      match modifier {
-        Modifier::Repeated => format_ident!("{to_snake}s"),
-        Modifier::Optional | Modifier::Regular => ident(&to_snake),
-      }
-    }
+  let field_name = match (label, &modifier) {
+    (Some(x), _) => label_name(x),
+    (None, Modifier::Repeated) => fn_name(&format!("{name}s")),
+    (None, Modifier::Optional | Modifier::Regular) => fn_name(name),
   };
   let idx = get_idx(counts, name);
   let ret_ty: TokenStream;
@@ -0,0 +1,203 @@
+use crate::{LexOptions, MakeOptions, Options, ParserOptions};
+use std::collections::HashMap;
+use std::hash::RandomState;
+use std::sync::LazyLock;
+
+static EMPTY: LazyLock<HashMap<&str, &str>> = LazyLock::new(HashMap::new);
+
+/// Validates the `grammar` with options changed by `f`, and checks the errors are `want`.
+fn check<'a, F>(grammar: &'a str, f: F, want: &[&str])
+where
+  F: FnOnce(&mut Options<'a, RandomState>),
+{
+  let mut opts = Options::new("Lang", &["Whitespace"], grammar, &EMPTY, &EMPTY, file!());
+  f(&mut opts);
+  let grammar = grammar.parse().unwrap();
+  let got: Vec<_> = crate::validate::get(&opts, &grammar).iter().map(ToString::to_string).collect();
+  assert_eq!(got, want);
+}
+
+fn lexer() -> LexOptions<'static> {
+  LexOptions {
+    whitespace: "Whitespace",
+    line_comment: None,
+    block_comment: None,
+    string: None,
+    number: None,
+    ident: "Name",
+    ident_start: None,
+    ident_continue: None,
+    hooks: &[],
+    error: "Whitespace",
+  }
+}
+
+#[test]
+fn ok() {
+  check("Root = Item*\nItem = 'Name' ';'", |_| {}, &[]);
+}
+
+#[test]
+fn empty() {
+  check("", |_| {}, &["grammar: there are no nodes"]);
+}
+
+#[test]
+fn mixed_alt() {
+  check(
+    "Root = Foo | 'Name' | ('a' 'b')\nFoo = 'Name'",
+    |_| {},
+    &[
+      "node `Root` at 3: each alternative must be a node or a token",
+      "node `Root`: an alt must be all nodes or all tokens",
+    ],
+  );
+}
+
+#[test]
+fn nested_field() {
+  check(
+    "Root = (Foo?)* a:b:Foo (Foo Foo)?\nFoo = 'Name'",
+    |_| {},
+    &[
+      "node `Root` at 1: cannot be optional or repeated more than once",
+      "node `Root` at 2: already have label `a`, cannot have new label `b`",
+      "node `Root` at 3: each field must be a node or a token, not a nested sequence or alt",
+    ],
+  );
+}
+
+#[test]
+fn comments_not_trivia() {
+  check(
+    "Root = 'Name'",
+    |opts| {
+      opts.comments = &["Comment"];
+      opts.doc_comments = &["Whitespace", "DocComment"];
+    },
+    &[
+      "option `comments`: `Comment` is not trivia",
+      "option `doc_comments`: `DocComment` is not trivia",
+    ],
+  );
+}
+
+#[test]
+fn unknown_special_and_doc() {
+  let doc = HashMap::from([("Nope", "no such token")]);
+  let special = HashMap::from([("Name", "a name"), ("Int", "an integer")]);
+  check(
+    "Root = 'Name'",
+    |opts| {
+      opts.doc = &doc;
+      opts.special = &special;
+    },
+    &[
+      "option `special`: no token `Int` in the grammar",
+      "option `doc`: no token `Nope` in the grammar",
+    ],
+  );
+}
+
+#[test]
+fn unknown_punctuation() {
+  check("Root = '§'", |_| {}, &["token `§`: don't know the name for `§`"]);
+}
+
+#[test]
+fn duplicate_and_invalid_names() {
+  check(
+    "Root = '(' LRound\nLRound = 'Name'",
+    |opts| {
+      opts.lang = "the lang";
+      opts.trivia = &["Whitespace", "Whitespace", "Not Ident"];
+    },
+    &[
+      "option `lang`: `the lang` is not a valid identifier",
+      "option `trivia`: more than one SyntaxKind is named `Whitespace`",
+      "option `trivia`: `Not Ident` is not a valid identifier",
+      "node `LRound`: more than one SyntaxKind is named `LRound`",
+    ],
+  );
+}
+
+#[test]
+fn unknown_override() {
+  check(
+    "Root = 'Name'",
+    |opts| opts.parser = Some(ParserOptions { overrides: &["Root", "Expr"] }),
+    &["option `parser`: no node `Expr` in the grammar"],
+  );
+}
+
+#[test]
+fn left_recursive() {
+  let grammar = "Root = Expr\nExpr = Call | Paren\nCall = Expr '(' ')'\nParen = '(' Expr ')'";
+  check(
+    grammar,
+    |opts| opts.parser = Some(ParserOptions { overrides: &[] }),
+    &[
+      "node `Expr`: left-recursive, so it must be overridden",
+      "node `Call`: left-recursive, so it must be overridden",
+    ],
+  );
+  check(grammar, |opts| opts.parser = Some(ParserOptions { overrides: &["Expr"] }), &[]);
+}
+
+#[test]
+fn not_ll1() {
+  let grammar = "Root = A | B | C\nA = 'x' 'y'\nB = 'z' | 'x'\nC = 'y'?";
+  check(
+    grammar,
+    |opts| opts.parser = Some(ParserOptions { overrides: &[] }),
+    &[
+      "node `Root` at 2: may start with the same tokens as alternative 1: `x`",
+      "node `Root` at 3: an alternative may not be empty",
+    ],
+  );
+  check(grammar, |opts| opts.parser = Some(ParserOptions { overrides: &["Root"] }), &[]);
+  check(grammar, |_| {}, &[]);
+}
+
+#[test]
+fn bad_lexer() {
+  let special = HashMap::from([("Name", "a name")]);
+  check(
+    "Root = 'Name'",
+    |opts| {
+      opts.special = &special;
+      opts.lexer =
+        Some(LexOptions { number: Some("Int"), ident_start: Some("not a path("), ..lexer() });
+    },
+    &["option `lexer`: no SyntaxKind `Int`", "option `lexer`: `not a path(` is not a path"],
+  );
+}
+
+#[test]
+fn bad_make() {
+  check(
+    "Root = 'Name'",
+    |opts| {
+      opts.make = Some(MakeOptions {
+        whitespace: "Space",
+        space: " ",
+        no_space_before: &[],
+        no_space_after: &[],
+      });
+    },
+    &["option `make`: no SyntaxKind `Space`"],
+  );
+}
+
+#[test]
+fn keyword_names() {
+  check("Root = Try Macro type:Try? elseBranch:Try?\nTry = 'Name'\nMacro = 'Name'", |_| {}, &[]);
+  check(
+    "Root = Self crate:Root?\nSelf = 'Name'",
+    |_| {},
+    &[
+      "node `Root` at 2: `crate` cannot be the name of a function or parameter",
+      "node `Self`: `Self` cannot be the name of a function or parameter",
+    ],
+  );
+}
@@ -50,24 +50,9 @@ impl TokenDb {
     let mut special = FxHashMap::default();
     for token in grammar.tokens() {
       let orig_name = grammar[token].name.as_str();
-      let kind: Kind;
-      let mut name: String;
-      let mut desc = None::<String>;
-      if let Some(&d) = special_desc.get(orig_name) {
-        kind = Kind::Special;
-        name = orig_name.to_owned();
-        desc = Some(d.to_owned());
-      } else if orig_name.chars().any(|c| c.is_ascii_alphabetic()) {
-        kind = Kind::Keyword;
-        name = identifier_case::snake_to_pascal(orig_name);
-        name.push_str("Kw");
-      } else {
-        kind = Kind::Punctuation;
-        name = String::new();
-        for c in orig_name.chars() {
-          name.push_str(char_name::get(c));
-        }
-      }
+      let desc = special_desc.get(orig_name).map(|&d| d.to_owned());
+      let (kind, name) = kind_and_name(orig_name, desc.is_some());
+      let name = name.unwrap_or_else(|c| panic!("don't know the name for {c}"));
       let tok = Token { name, desc, doc: doc.get(orig_name).map(|&x| x.to_owned()) };
       match kind {
         Kind::Punctuation => {
@@ -93,6 +78,21 @@ impl TokenDb {
   }
 }
 
+/// Returns the kind of the token with the name from the grammar, and the name of its `SyntaxKind`,
+/// or the char in it with no known name if it is punctuation.
+pub(crate) fn kind_and_name(orig_name: &str, is_special: bool) -> (Kind, Result<String, char>) {
+  if is_special {
+    (Kind::Special, Ok(orig_name.to_owned()))
+  } else if orig_name.chars().any(|c| c.is_ascii_alphabetic()) {
+    let mut name = identifier_case::snake_to_pascal(orig_name);
+    name.push_str("Kw");
+    (Kind::Keyword, Ok(name))
+  } else {
+    let name = orig_name.chars().map(|c| char_name::get_opt(c).ok_or(c)).collect();
+    (Kind::Punctuation, name)
+  }
+}
+
 pub(crate) fn ident(s: &str) -> proc_macro2::Ident {
   quote::format_ident!("{}", s)
 }
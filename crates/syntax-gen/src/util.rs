@@ -1,5 +1,6 @@
-use crate::token::TokenDb;
+use crate::token::{TokenDb, ident};
 use fast_hash::FxHashSet;
+use identifier_case::pascal_to_snake;
 use proc_macro2::Ident;
 use ungrammar::{Grammar, Node, Rule, Token};
 
@@ -11,6 +12,10 @@ pub(crate) struct Cx {
   pub(crate) token_alts: FxHashSet<Ident>,
 }
 
+pub(crate) fn node_named(grammar: &Grammar, name: &str) -> Option<Node> {
+  grammar.iter().find(|&node| grammar[node].name == name)
+}
+
 pub(crate) fn unwrap_node(rule: &Rule) -> Node {
   match rule {
     Rule::Node(node) => *node,
@@ -24,3 +29,94 @@ pub(crate) fn unwrap_token(rule: &Rule) -> Token {
     _ => panic!("unwrap_token on {rule:?}"),
   }
 }
+
+/// Returns the snake case version of the name, as an identifier for a function or variable. It is
+/// a raw identifier if it would otherwise be a keyword.
+///
+/// Panics if the name is a keyword that cannot be a raw identifier, which validation rejects.
+pub(crate) fn fn_name(name: &str) -> Ident {
+  label_name(&pascal_to_snake(name))
+}
+
+/// Returns whether [`fn_name`] can make an identifier for the name.
+pub(crate) fn is_fn_name(name: &str) -> bool {
+  is_label_name(&pascal_to_snake(name))
+}
+
+/// Returns the label, spelled as it is, as an identifier for a function or variable. It is a raw
+/// identifier if it would otherwise be a keyword.
+///
+/// Panics if the label is a keyword that cannot be a raw identifier, which validation rejects.
+pub(crate) fn label_name(label: &str) -> Ident {
+  assert!(is_label_name(label), "cannot be a raw identifier: {label}");
+  if is_keyword(label) {
+    Ident::new_raw(label, proc_macro2::Span::call_site())
+  } else {
+    ident(label)
+  }
+}
+
+/// Returns whether [`label_name`] can make an identifier for the label.
+pub(crate) fn is_label_name(label: &str) -> bool {
+  !is_non_raw_keyword(label)
+}
+
+/// The keywords which are not allowed to be raw identifiers.
+fn is_non_raw_keyword(s: &str) -> bool {
+  matches!(s, "crate" | "self" | "Self" | "super")
+}
+
+/// The strict and reserved keywords, as of the 2024 edition.
+fn is_keyword(s: &str) -> bool {
+  matches!(
+    s,
+    "abstract"
+      | "as"
+      | "async"
+      | "await"
+      | "become"
+      | "box"
+      | "break"
+      | "const"
+      | "continue"
+      | "do"
+      | "dyn"
+      | "else"
+      | "enum"
+      | "extern"
+      | "false"
+      | "final"
+      | "fn"
+      | "for"
+      | "gen"
+      | "if"
+      | "impl"
+      | "in"
+      | "let"
+      | "loop"
+      | "macro"
+      | "match"
+      | "mod"
+      | "move"
+      | "mut"
+      | "override"
+      | "priv"
+      | "pub"
+      | "ref"
+      | "return"
+      | "static"
+      | "struct"
+      | "trait"
+      | "true"
+      | "try"
+      | "type"
+      | "typeof"
+      | "unsafe"
+      | "unsized"
+      | "use"
+      | "virtual"
+      | "where"
+      | "while"
+      | "yield"
+  )
+}
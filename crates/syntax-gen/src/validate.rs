@@ -0,0 +1,256 @@
+use crate::error::{Error, ErrorKind, Location};
+use crate::parse::FirstSets;
+use crate::token::kind_and_name;
+use crate::util::{is_fn_name, is_label_name, node_named};
+use crate::{LexOptions, Options};
+use fast_hash::{FxHashMap, FxHashSet};
+use std::hash::BuildHasher;
+use ungrammar::{Grammar, Rule};
+
+/// Returns all the errors in the options, so that generating code from them will not panic.
+pub(crate) fn get<S>(opts: &Options<'_, S>, grammar: &Grammar) -> Vec<Error>
+where
+  S: BuildHasher,
+{
+  let mut errors = Vec::<Error>::new();
+  if !is_ident(opts.lang) {
+    let kind = ErrorKind::InvalidName(opts.lang.to_owned());
+    errors.push(Error { location: Location::Options("lang"), kind });
+  }
+  if grammar.iter().next().is_none() {
+    errors.push(Error { location: Location::Grammar, kind: ErrorKind::NoNodes });
+  }
+  for node in grammar.iter() {
+    let data = &grammar[node];
+    if is_ident(&data.name) && !is_fn_name(&data.name) {
+      let location = Location::Node { name: data.name.clone(), path: Vec::new() };
+      errors.push(Error { location, kind: ErrorKind::InvalidFnName(data.name.clone()) });
+    }
+    node_rule(&mut errors, &data.name, &data.rule);
+  }
+  let tokens: FxHashSet<_> = grammar.tokens().map(|tok| grammar[tok].name.as_str()).collect();
+  for (field, names) in [("comments", opts.comments), ("doc_comments", opts.doc_comments)] {
+    for &name in names {
+      if !opts.trivia.contains(&name) {
+        errors.push(Error {
+          location: Location::Options(field),
+          kind: ErrorKind::NotTrivia(name.to_owned()),
+        });
+      }
+    }
+  }
+  for (field, map) in [("special", opts.special), ("doc", opts.doc)] {
+    let mut names: Vec<_> = map.keys().filter(|name| !tokens.contains(*name)).collect();
+    names.sort_unstable();
+    for name in names {
+      errors.push(Error {
+        location: Location::Options(field),
+        kind: ErrorKind::NoSuchToken((*name).to_owned()),
+      });
+    }
+  }
+  let kinds = kind_names(opts, grammar, &mut errors);
+  if let Some(parser) = &opts.parser {
+    let mut overrides = FxHashSet::default();
+    for &name in parser.overrides {
+      match node_named(grammar, name) {
+        Some(node) => {
+          overrides.insert(node);
+        }
+        None => errors.push(Error {
+          location: Location::Options("parser"),
+          kind: ErrorKind::NoSuchNode(name.to_owned()),
+        }),
+      }
+    }
+    let first = FirstSets::new(grammar);
+    let left_recursive = first.left_recursive(&overrides);
+    for &node in &left_recursive {
+      errors.push(Error {
+        location: Location::Node { name: grammar[node].name.clone(), path: Vec::new() },
+        kind: ErrorKind::LeftRecursive,
+      });
+    }
+    for node in grammar.iter() {
+      if let Rule::Alt(rules) = &grammar[node].rule
+        && !overrides.contains(&node)
+        && !left_recursive.contains(&node)
+      {
+        alt_first(&mut errors, grammar, &first, &grammar[node].name, rules);
+      }
+    }
+  }
+  if let Some(lexer) = &opts.lexer {
+    for name in lex_kinds(lexer) {
+      if !kinds.contains(name) {
+        let kind = ErrorKind::NoSuchKind(name.to_owned());
+        errors.push(Error { location: Location::Options("lexer"), kind });
+      }
+    }
+    let paths = lexer.hooks.iter().chain(&lexer.ident_start).chain(&lexer.ident_continue);
+    for &path in paths {
+      if path.parse::<proc_macro2::TokenStream>().is_err() {
+        let kind = ErrorKind::InvalidPath(path.to_owned());
+        errors.push(Error { location: Location::Options("lexer"), kind });
+      }
+    }
+  }
+  if let Some(make) = &opts.make
+    && !kinds.contains(make.whitespace)
+  {
+    let kind = ErrorKind::NoSuchKind(make.whitespace.to_owned());
+    errors.push(Error { location: Location::Options("make"), kind });
+  }
+  errors
+}
+
+/// Checks the names of all the `SyntaxKind`s, and returns them.
+fn kind_names<S>(
+  opts: &Options<'_, S>,
+  grammar: &Grammar,
+  errors: &mut Vec<Error>,
+) -> FxHashSet<String>
+where
+  S: BuildHasher,
+{
+  // the names of all the SyntaxKinds, and where each comes from.
+  let mut kinds: Vec<(String, Location)> =
+    opts.trivia.iter().map(|&x| (x.to_owned(), Location::Options("trivia"))).collect();
+  for tok in grammar.tokens() {
+    let name = grammar[tok].name.as_str();
+    let location = Location::Token(name.to_owned());
+    match kind_and_name(name, opts.special.contains_key(name)).1 {
+      Ok(kind) => kinds.push((kind, location)),
+      Err(c) => errors.push(Error { location, kind: ErrorKind::UnknownPunctuation(c) }),
+    }
+  }
+  kinds.extend(grammar.iter().map(|node| {
+    let name = grammar[node].name.clone();
+    let location = Location::Node { name: name.clone(), path: Vec::new() };
+    (name, location)
+  }));
+  let mut counts = FxHashMap::<String, usize>::default();
+  for (kind, location) in kinds {
+    if !is_ident(&kind) {
+      errors.push(Error { location: location.clone(), kind: ErrorKind::InvalidName(kind.clone()) });
+    }
+    let count = counts.entry(kind.clone()).or_default();
+    *count += 1;
+    // only report each name once, at the second thing with it.
+    if *count == 2 {
+      errors.push(Error { location, kind: ErrorKind::DuplicateName(kind) });
+    }
+  }
+  counts.into_keys().collect()
+}
+
+/// Checks the generated parser can pick an alternative of the alt by looking at one token.
+fn alt_first(
+  errors: &mut Vec<Error>,
+  grammar: &Grammar,
+  first: &FirstSets<'_>,
+  name: &str,
+  rules: &[Rule],
+) {
+  let firsts: Vec<_> = rules.iter().map(|rule| first.get(rule)).collect();
+  for (idx, cur) in firsts.iter().enumerate() {
+    let mut push = |kind: ErrorKind| {
+      errors
+        .push(Error { location: Location::Node { name: name.to_owned(), path: vec![idx] }, kind });
+    };
+    if cur.nullable {
+      push(ErrorKind::NullableAlt);
+    }
+    for (prev_idx, prev) in firsts[..idx].iter().enumerate() {
+      let mut tokens: Vec<_> =
+        cur.tokens.intersection(&prev.tokens).map(|&tok| grammar[tok].name.clone()).collect();
+      if !tokens.is_empty() {
+        tokens.sort_unstable();
+        push(ErrorKind::OverlappingAlt(prev_idx, tokens));
+      }
+    }
+  }
+}
+
+/// Checks the shape of the rule for a node is one we can generate code for.
+fn node_rule(errors: &mut Vec<Error>, name: &str, rule: &Rule) {
+  let mut push = |path: Vec<usize>, kind: ErrorKind| {
+    errors.push(Error { location: Location::Node { name: name.to_owned(), path }, kind });
+  };
+  match rule {
+    Rule::Alt(rules) => {
+      let mut nodes = false;
+      let mut tokens = false;
+      for (idx, rule) in rules.iter().enumerate() {
+        match rule {
+          Rule::Node(_) => nodes = true,
+          Rule::Token(_) => tokens = true,
+          _ => push(vec![idx], ErrorKind::BadAltRule),
+        }
+      }
+      if nodes && tokens {
+        push(Vec::new(), ErrorKind::MixedAlt);
+      }
+    }
+    Rule::Seq(rules) => {
+      for (idx, rule) in rules.iter().enumerate() {
+        if let Some(kind) = field(rule) {
+          push(vec![idx], kind);
+        }
+      }
+    }
+    rule => {
+      if let Some(kind) = field(rule) {
+        push(Vec::new(), kind);
+      }
+    }
+  }
+}
+
+/// Returns the error in the rule for a field, if any.
+fn field(mut rule: &Rule) -> Option<ErrorKind> {
+  let mut label: Option<&str> = None;
+  let mut modified = false;
+  loop {
+    match rule {
+      Rule::Node(_) | Rule::Token(_) => return None,
+      Rule::Labeled { label: l, rule: r } => {
+        if let Some(old) = label {
+          return Some(ErrorKind::TwoLabels(old.to_owned(), l.clone()));
+        }
+        if !is_ident(l) {
+          return Some(ErrorKind::InvalidName(l.clone()));
+        }
+        if !is_label_name(l) {
+          return Some(ErrorKind::InvalidFnName(l.clone()));
+        }
+        label = Some(l.as_str());
+        rule = r.as_ref();
+      }
+      Rule::Opt(r) | Rule::Rep(r) => {
+        if modified {
+          return Some(ErrorKind::NestedModifier);
+        }
+        modified = true;
+        rule = r.as_ref();
+      }
+      Rule::Seq(_) | Rule::Alt(_) => return Some(ErrorKind::BadFieldRule),
+    }
+  }
+}
+
+fn lex_kinds<'a>(lexer: &LexOptions<'a>) -> Vec<&'a str> {
+  let mut ret = vec![lexer.whitespace, lexer.ident, lexer.error];
+  ret.extend(lexer.line_comment.map(|(_, kind)| kind));
+  ret.extend(lexer.block_comment.as_ref().map(|bc| bc.kind));
+  ret.extend(lexer.string.as_ref().map(|q| q.kind));
+  ret.extend(lexer.number);
+  ret
+}
+
+fn is_ident(s: &str) -> bool {
+  let mut chars = s.chars();
+  chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+    && chars.all(|c| c.is_alphanumeric() || c == '_')
+    && s != "_"
+}
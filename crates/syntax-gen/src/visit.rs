@@ -0,0 +1,259 @@
+use crate::VisitOptions;
+use crate::token::ident;
+use crate::util::{Cx, unwrap_node};
+use identifier_case::pascal_to_snake;
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use ungrammar::{Node, Rule};
+
+pub(crate) fn get(cx: &Cx, opts: &VisitOptions, file: &str) -> TokenStream {
+  let lang = &cx.lang;
+  let mut visit_methods = Vec::<TokenStream>::new();
+  let mut walk_fns = Vec::<TokenStream>::new();
+  let mut fold_methods = Vec::<TokenStream>::new();
+  let mut walk_fold_fns = Vec::<TokenStream>::new();
+  let mut seq_visits = Vec::<TokenStream>::new();
+  let mut seq_folds = Vec::<TokenStream>::new();
+  for node in cx.grammar.iter() {
+    let data = &cx.grammar[node];
+    let name = ident(&data.name);
+    if cx.token_alts.contains(&name) {
+      continue;
+    }
+    let Names { visit, walk, fold, walk_fold } = Names::new(&data.name);
+    visit_methods.push(quote! {
+      fn #visit(&mut self, node: &ast::#name) {
+        #walk(self, node);
+      }
+    });
+    fold_methods.push(quote! {
+      fn #fold(&mut self, node: ast::#name) -> ast::#name {
+        #walk_fold(self, node)
+      }
+    });
+    let (walk_fn, walk_fold_fn) = match &data.rule {
+      Rule::Alt(rules) => alt(cx, &name, rules),
+      rule => {
+        seq_visits.push(quote! { SK::#name => v.#visit(&cast(node)) });
+        seq_folds.push(quote! { SK::#name => f.#fold(cast(node)).syntax().clone() });
+        seq(cx, &name, rule)
+      }
+    };
+    walk_fns.push(walk_fn);
+    walk_fold_fns.push(walk_fold_fn);
+  }
+  let fold = if opts.fold {
+    quote! {
+      /// Rewrites the nodes of a tree. By default, each method rewrites the children of the node.
+      pub trait Fold {
+        #(#fold_methods)*
+      }
+
+      /// Rewrites the node with the method for its kind, if any.
+      pub fn fold<F>(f: &mut F, node: SyntaxNode) -> SyntaxNode
+      where
+        F: Fold + ?Sized,
+      {
+        match node.kind() {
+          #(#seq_folds ,)*
+          _ => node,
+        }
+      }
+
+      /// Returns a copy of the node with each child node replaced by `f` applied to it.
+      #[allow(unused)]
+      fn rebuild<N, G>(node: &N, mut f: G) -> N
+      where
+        N: ast::AstNode<Language = #lang>,
+        G: FnMut(SyntaxNode) -> SyntaxNode,
+      {
+        let children: Vec<_> = node
+          .syntax()
+          .children_with_tokens()
+          .map(|child| match child {
+            rowan::NodeOrToken::Node(child) => {
+              rowan::NodeOrToken::Node(f(child).green().into_owned())
+            }
+            rowan::NodeOrToken::Token(tok) => rowan::NodeOrToken::Token(tok.green().to_owned()),
+          })
+          .collect();
+        let green = rowan::GreenNode::new(node.syntax().kind().into(), children);
+        cast(SyntaxNode::new_root(green))
+      }
+
+      #(#walk_fold_fns)*
+    }
+  } else {
+    quote! {}
+  };
+  quote! {
+    use crate::ast::{self, AstNode as _};
+    use crate::kind::{SyntaxKind as SK, SyntaxNode, #lang};
+
+    pub const GENERATED_BY: &str = #file;
+
+    /// Visits the nodes of a tree. By default, each method visits the children of the node.
+    pub trait Visitor {
+      #(#visit_methods)*
+    }
+
+    /// Visits the node with the method for its kind, if any.
+    pub fn walk<V>(v: &mut V, node: &SyntaxNode)
+    where
+      V: Visitor + ?Sized,
+    {
+      let node = node.clone();
+      match node.kind() {
+        #(#seq_visits ,)*
+        _ => {}
+      }
+    }
+
+    fn cast<N>(node: SyntaxNode) -> N
+    where
+      N: ast::AstNode<Language = #lang>,
+    {
+      N::cast(node).expect("node kind does not match its AST type")
+    }
+
+    #(#walk_fns)*
+
+    #fold
+  }
+}
+
+/// Returns the walk and fold functions for an alt, which dispatch on the variant.
+fn alt(cx: &Cx, name: &Ident, rules: &[Rule]) -> (TokenStream, TokenStream) {
+  let Names { walk, walk_fold, .. } = Names::new(&name.to_string());
+  let variants: Vec<_> = rules.iter().map(|rule| variant(cx, unwrap_node(rule))).collect();
+  let visit_arms = variants.iter().map(|(variant, visit, _)| {
+    quote! { ast::#name::#variant(x) => v.#visit(x) }
+  });
+  let fold_arms = variants.iter().map(|(variant, _, fold)| {
+    quote! { ast::#name::#variant(x) => ast::#name::#variant(f.#fold(x)) }
+  });
+  let walk_fn = quote! {
+    pub fn #walk<V>(v: &mut V, node: &ast::#name)
+    where
+      V: Visitor + ?Sized,
+    {
+      match node {
+        #(#visit_arms ,)*
+      }
+    }
+  };
+  let walk_fold_fn = quote! {
+    pub fn #walk_fold<F>(f: &mut F, node: ast::#name) -> ast::#name
+    where
+      F: Fold + ?Sized,
+    {
+      match node {
+        #(#fold_arms ,)*
+      }
+    }
+  };
+  (walk_fn, walk_fold_fn)
+}
+
+/// Returns the walk and fold functions for a sequence, which go through the child nodes.
+fn seq(cx: &Cx, name: &Ident, rule: &Rule) -> (TokenStream, TokenStream) {
+  let Names { walk, walk_fold, .. } = Names::new(&name.to_string());
+  let mut children = Vec::<Node>::new();
+  node_children(cx, rule, &mut children);
+  let children: Vec<_> = children.into_iter().map(|node| variant(cx, node)).collect();
+  let (walk_body, walk_fold_body) = if children.is_empty() {
+    (quote! {}, quote! { node })
+  } else {
+    let visits = children.iter().map(|(ty, visit, _)| {
+      quote! { if let Some(x) = ast::#ty::cast(child.clone()) { v.#visit(&x); } }
+    });
+    let folds = children.iter().map(|(ty, _, fold)| {
+      quote! {
+        if let Some(x) = ast::#ty::cast(child.clone()) {
+          return f.#fold(x).syntax().clone();
+        }
+      }
+    });
+    let walk_body = quote! {
+      for child in node.syntax().children() {
+        #(#visits)else*
+      }
+    };
+    let walk_fold_body = quote! {
+      rebuild(&node, |child| {
+        #(#folds)*
+        child
+      })
+    };
+    (walk_body, walk_fold_body)
+  };
+  // with no children, the walk does nothing and the fold returns the node as is.
+  let (v, f, node) = if children.is_empty() {
+    (quote! { _ }, quote! { _ }, quote! { _ })
+  } else {
+    (quote! { v }, quote! { f }, quote! { node })
+  };
+  let walk_fn = quote! {
+    pub fn #walk<V>(#v: &mut V, #node: &ast::#name)
+    where
+      V: Visitor + ?Sized,
+    {
+      #walk_body
+    }
+  };
+  let walk_fold_fn = quote! {
+    pub fn #walk_fold<F>(#f: &mut F, node: ast::#name) -> ast::#name
+    where
+      F: Fold + ?Sized,
+    {
+      #walk_fold_body
+    }
+  };
+  (walk_fn, walk_fold_fn)
+}
+
+/// The names of the generated methods and functions for a node.
+struct Names {
+  visit: Ident,
+  walk: Ident,
+  fold: Ident,
+  walk_fold: Ident,
+}
+
+impl Names {
+  fn new(name: &str) -> Self {
+    let snake = pascal_to_snake(name);
+    Self {
+      visit: format_ident!("visit_{snake}"),
+      walk: format_ident!("walk_{snake}"),
+      fold: format_ident!("fold_{snake}"),
+      walk_fold: format_ident!("walk_fold_{snake}"),
+    }
+  }
+}
+
+/// Returns the type name of the node, and the names of the visit and fold methods for it.
+fn variant(cx: &Cx, node: Node) -> (Ident, Ident, Ident) {
+  let name = cx.grammar[node].name.as_str();
+  let Names { visit, fold, .. } = Names::new(name);
+  (ident(name), visit, fold)
+}
+
+/// Pushes the distinct nodes which are children in the rule, except for token alts, since those are
+/// tokens in the tree.
+fn node_children(cx: &Cx, rule: &Rule, out: &mut Vec<Node>) {
+  match rule {
+    Rule::Labeled { rule, .. } | Rule::Opt(rule) | Rule::Rep(rule) => node_children(cx, rule, out),
+    Rule::Node(node) => {
+      if !cx.token_alts.contains(&ident(&cx.grammar[*node].name)) && !out.contains(node) {
+        out.push(*node);
+      }
+    }
+    Rule::Token(_) => {}
+    Rule::Seq(rules) | Rule::Alt(rules) => {
+      for rule in rules {
+        node_children(cx, rule, out);
+      }
+    }
+  }
+}
@@ -0,0 +1,274 @@
+//! A table-driven lexer for the common cases.
+//!
+//! Configure it with a [`Config`], then call [`get`] to lex a string into [`Token`]s. Most of the
+//! configuration comes straight from what syntax-gen generates for a `SyntaxKind`, e.g.:
+//!
+//! ```ignore
+//! let config = lex::Config {
+//!   punctuation: &SyntaxKind::PUNCTUATION,
+//!   keyword: SyntaxKind::keyword,
+//!   ..
+//! };
+//! ```
+
+use crate::Token;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+/// The configuration for the lexer.
+///
+/// At each position, the lexer tries each kind of token in the order of these fields, and takes the
+/// first one that matches.
+#[derive(Debug, Clone, Copy)]
+pub struct Config<'a, K> {
+  /// Functions to try before everything else, for tokens not handled by the rest of the config, like
+  /// raw strings or numbers with suffixes. The first one to return `Some` is used.
+  pub hooks: &'a [Hook<K>],
+  /// The kind for a run of whitespace.
+  pub whitespace: K,
+  /// The start of a line comment, and the kind for line comments. A line comment goes up to but not
+  /// including the next newline.
+  pub line_comment: Option<(&'a str, K)>,
+  /// Block comments.
+  pub block_comment: Option<BlockComment<'a, K>>,
+  /// String literals.
+  pub string: Option<Quoted<K>>,
+  /// The kind for number literals, which start with an ASCII digit and continue with ASCII
+  /// alphanumerics, `_`, and `.` when followed by an ASCII digit.
+  pub number: Option<K>,
+  /// Returns whether a char may start an identifier.
+  pub ident_start: fn(char) -> bool,
+  /// Returns whether a char may continue an identifier.
+  pub ident_continue: fn(char) -> bool,
+  /// Returns the kind of the keyword with the given text, or `None` if the text is not a keyword,
+  /// as with `SyntaxKind::keyword`.
+  pub keyword: fn(&[u8]) -> Option<K>,
+  /// The kind for identifiers which are not keywords.
+  pub ident: K,
+  /// The punctuation and their kinds, as with `SyntaxKind::PUNCTUATION`. The longest match is
+  /// used.
+  pub punctuation: &'a [(&'a [u8], K)],
+  /// The kind for a char which starts no token.
+  pub error: K,
+}
+
+/// Block comments, like `/* ... */`.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockComment<'a, K> {
+  /// The start of a block comment.
+  pub open: &'a str,
+  /// The end of a block comment.
+  pub close: &'a str,
+  /// Whether block comments nest, as in `/* a /* b */ c */`.
+  pub nested: bool,
+  /// The kind.
+  pub kind: K,
+}
+
+/// String literals, like `"a\"b"`.
+#[derive(Debug, Clone, Copy)]
+pub struct Quoted<K> {
+  /// The char which starts and ends the literal.
+  pub quote: char,
+  /// The char which makes the char after it part of the literal even if it's the `quote`.
+  pub escape: Option<char>,
+  /// The kind.
+  pub kind: K,
+}
+
+/// A function to lex a token at the start of a string, or return `None` if there is no such token
+/// there.
+pub type Hook<K> = fn(&str) -> Option<Lexed<K>>;
+
+/// A token lexed by a [`Hook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lexed<K> {
+  /// The kind.
+  pub kind: K,
+  /// The length in bytes. It must be more than 0 and at a char boundary.
+  pub len: usize,
+  /// An error for the token, if any.
+  pub error: Option<&'static str>,
+}
+
+/// The result of lexing.
+#[derive(Debug)]
+pub struct Lex<'a, K> {
+  /// The tokens, which together make up the whole string.
+  pub tokens: Vec<Token<'a, K>>,
+  /// The errors.
+  pub errors: Vec<Error>,
+}
+
+/// An error when lexing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+  /// The range of the error in the string.
+  pub range: Range<usize>,
+  /// The kind of error.
+  pub kind: ErrorKind,
+}
+
+/// A kind of error when lexing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+  /// A char which starts no token.
+  InvalidSource,
+  /// A block comment with no end.
+  UnclosedBlockComment,
+  /// A string literal with no end.
+  UnclosedString,
+  /// An error from a [`Hook`].
+  Hook(&'static str),
+}
+
+impl fmt::Display for ErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ErrorKind::InvalidSource => f.write_str("invalid source"),
+      ErrorKind::UnclosedBlockComment => f.write_str("unclosed block comment"),
+      ErrorKind::UnclosedString => f.write_str("unclosed string literal"),
+      ErrorKind::Hook(msg) => f.write_str(msg),
+    }
+  }
+}
+
+/// Lexes the string.
+///
+/// # Panics
+///
+/// If a hook returned an invalid length.
+#[must_use]
+pub fn get<'a, K>(s: &'a str, config: &Config<'_, K>) -> Lex<'a, K>
+where
+  K: Copy,
+{
+  let mut ret = Lex { tokens: Vec::new(), errors: Vec::new() };
+  let mut idx = 0;
+  while idx < s.len() {
+    let (kind, len, error) = token(&s[idx..], config);
+    assert!(0 < len && s.is_char_boundary(idx + len), "invalid token length {len} at {idx}");
+    let end = idx + len;
+    if let Some(kind) = error {
+      ret.errors.push(Error { range: idx..end, kind });
+    }
+    ret.tokens.push(Token { kind, text: &s[idx..end] });
+    idx = end;
+  }
+  ret
+}
+
+/// Returns the kind and length of the token at the start of the non-empty `s`, and its error if
+/// any.
+fn token<K>(s: &str, config: &Config<'_, K>) -> (K, usize, Option<ErrorKind>)
+where
+  K: Copy,
+{
+  for hook in config.hooks {
+    if let Some(lexed) = hook(s) {
+      return (lexed.kind, lexed.len, lexed.error.map(ErrorKind::Hook));
+    }
+  }
+  let c = s.chars().next().expect("empty string");
+  if c.is_whitespace() {
+    return (config.whitespace, prefix_len(s, char::is_whitespace), None);
+  }
+  if let Some((start, kind)) = config.line_comment
+    && s.starts_with(start)
+  {
+    return (kind, s.find('\n').unwrap_or(s.len()), None);
+  }
+  if let Some(bc) = config.block_comment
+    && s.starts_with(bc.open)
+  {
+    return match block_comment(s, &bc) {
+      Some(len) => (bc.kind, len, None),
+      None => (bc.kind, s.len(), Some(ErrorKind::UnclosedBlockComment)),
+    };
+  }
+  if let Some(q) = config.string
+    && c == q.quote
+  {
+    return match quoted(s, &q) {
+      Some(len) => (q.kind, len, None),
+      None => (q.kind, s.len(), Some(ErrorKind::UnclosedString)),
+    };
+  }
+  if let Some(kind) = config.number
+    && c.is_ascii_digit()
+  {
+    return (kind, number(s), None);
+  }
+  if (config.ident_start)(c) {
+    let len = c.len_utf8() + prefix_len(&s[c.len_utf8()..], config.ident_continue);
+    let kind = (config.keyword)(&s.as_bytes()[..len]).unwrap_or(config.ident);
+    return (kind, len, None);
+  }
+  let punctuation = config
+    .punctuation
+    .iter()
+    .filter(|(bs, _)| !bs.is_empty() && s.as_bytes().starts_with(bs))
+    .max_by_key(|(bs, _)| bs.len());
+  if let Some(&(bs, kind)) = punctuation {
+    return (kind, bs.len(), None);
+  }
+  (config.error, c.len_utf8(), Some(ErrorKind::InvalidSource))
+}
+
+/// Returns the length of the longest prefix of `s` whose chars all satisfy `f`.
+fn prefix_len(s: &str, f: fn(char) -> bool) -> usize {
+  s.find(|c| !f(c)).unwrap_or(s.len())
+}
+
+/// Returns the length of the block comment at the start of `s`, or `None` if it has no end.
+fn block_comment<K>(s: &str, bc: &BlockComment<'_, K>) -> Option<usize> {
+  let mut depth = 0usize;
+  let mut idx = 0;
+  while idx < s.len() {
+    let rest = &s[idx..];
+    if depth != 0 && rest.starts_with(bc.close) {
+      idx += bc.close.len();
+      depth -= 1;
+      if depth == 0 {
+        return Some(idx);
+      }
+    } else if rest.starts_with(bc.open) && (depth == 0 || bc.nested) {
+      idx += bc.open.len();
+      depth += 1;
+    } else {
+      idx += rest.chars().next().map_or(1, char::len_utf8);
+    }
+  }
+  None
+}
+
+/// Returns the length of the string literal at the start of `s`, or `None` if it has no end.
+fn quoted<K>(s: &str, q: &Quoted<K>) -> Option<usize> {
+  let mut chars = s.char_indices().skip(1);
+  while let Some((idx, c)) = chars.next() {
+    if c == q.quote {
+      return Some(idx + c.len_utf8());
+    }
+    if Some(c) == q.escape {
+      chars.next();
+    }
+  }
+  None
+}
+
+/// Returns the length of the number literal at the start of `s`.
+fn number(s: &str) -> usize {
+  let bs = s.as_bytes();
+  let mut idx = 0;
+  while let Some(&b) = bs.get(idx) {
+    let ok = b.is_ascii_alphanumeric()
+      || b == b'_'
+      || (b == b'.' && bs.get(idx + 1).is_some_and(u8::is_ascii_digit));
+    if !ok {
+      break;
+    }
+    idx += 1;
+  }
+  idx
+}
@@ -2,6 +2,16 @@
 
 #![no_std]
 
+extern crate alloc;
+
+pub mod lex;
+#[cfg(feature = "owned")]
+pub mod owned;
+pub mod stream;
+
+#[cfg(test)]
+mod tests;
+
 /// A token, a pair of syntax kind and text.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Token<'a, K> {
@@ -11,8 +21,45 @@ pub struct Token<'a, K> {
   pub text: &'a str,
 }
 
+impl<K> Token<'_, K>
+where
+  K: Triviable,
+{
+  /// Returns the class of trivia this token is, or `None` if it is not trivia.
+  ///
+  /// This is the [`Triviable::trivia_class`] of the kind, except that whitespace containing a
+  /// newline is [`TriviaClass::Newline`].
+  #[must_use]
+  pub fn trivia_class(&self) -> Option<TriviaClass> {
+    match self.kind.trivia_class()? {
+      TriviaClass::Whitespace if self.text.contains('\n') => Some(TriviaClass::Newline),
+      class => Some(class),
+    }
+  }
+}
+
 /// Types whose values can report whether they are trivia or not.
 pub trait Triviable {
   /// Returns whether this is trivia.
   fn is_trivia(&self) -> bool;
+
+  /// Returns the class of trivia this is, or `None` if it is not trivia.
+  ///
+  /// Defaults to [`TriviaClass::Whitespace`] for all trivia.
+  fn trivia_class(&self) -> Option<TriviaClass> {
+    self.is_trivia().then_some(TriviaClass::Whitespace)
+  }
+}
+
+/// A class of trivia.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriviaClass {
+  /// Whitespace. For a [`Token`], it contains no newlines.
+  Whitespace,
+  /// Whitespace including at least one newline.
+  Newline,
+  /// A comment.
+  Comment,
+  /// A doc comment, which documents what comes after it.
+  DocComment,
 }
@@ -0,0 +1,65 @@
+//! Tokens which own their text.
+
+use crate::Token;
+use crate::stream::TokenStream;
+use str_util::SmolStr;
+
+/// A token which owns its text, unlike a [`Token`].
+///
+/// The text is a [`SmolStr`], so short text is stored inline and clones are cheap.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedToken<K> {
+  /// The kind of token.
+  pub kind: K,
+  /// The text of the token.
+  pub text: SmolStr,
+}
+
+impl<K> OwnedToken<K>
+where
+  K: Copy,
+{
+  /// Returns this as a borrowed token.
+  #[must_use]
+  pub fn as_token(&self) -> Token<'_, K> {
+    Token { kind: self.kind, text: self.text.as_str() }
+  }
+}
+
+impl<K> From<Token<'_, K>> for OwnedToken<K> {
+  fn from(token: Token<'_, K>) -> Self {
+    Self { kind: token.kind, text: SmolStr::new(token.text) }
+  }
+}
+
+impl<'a, K> From<&'a OwnedToken<K>> for Token<'a, K>
+where
+  K: Copy,
+{
+  fn from(token: &'a OwnedToken<K>) -> Self {
+    token.as_token()
+  }
+}
+
+impl<K> TokenStream<K>
+where
+  K: Copy,
+{
+  /// Returns the token at `idx` as an owned token, or `None` if there is no such token.
+  #[must_use]
+  pub fn get_owned(&self, idx: usize) -> Option<OwnedToken<K>> {
+    self.get(idx).map(OwnedToken::from)
+  }
+}
+
+impl<'a, K> Extend<&'a OwnedToken<K>> for TokenStream<K>
+where
+  K: Copy + 'a,
+{
+  fn extend<I>(&mut self, iter: I)
+  where
+    I: IntoIterator<Item = &'a OwnedToken<K>>,
+  {
+    self.extend(iter.into_iter().map(OwnedToken::as_token));
+  }
+}
@@ -0,0 +1,109 @@
+//! Storing many tokens without borrowing their text.
+
+use crate::Token;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// A sequence of tokens, whose text is stored in one string.
+///
+/// Unlike a `Vec<Token<'a, K>>`, this owns the text, so it may be kept after the text the tokens
+/// came from is dropped.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TokenStream<K> {
+  text: String,
+  /// The kind of each token, and where it ends in the text. Each token starts where the previous one
+  /// ended.
+  tokens: Vec<(K, usize)>,
+}
+
+impl<K> Default for TokenStream<K> {
+  fn default() -> Self {
+    Self { text: String::new(), tokens: Vec::new() }
+  }
+}
+
+impl<K> TokenStream<K>
+where
+  K: Copy,
+{
+  /// Adds the token to the end.
+  pub fn push(&mut self, token: Token<'_, K>) {
+    self.text.push_str(token.text);
+    self.tokens.push((token.kind, self.text.len()));
+  }
+
+  /// Returns the number of tokens.
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.tokens.len()
+  }
+
+  /// Returns whether there are no tokens.
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.tokens.is_empty()
+  }
+
+  /// Returns the text of all the tokens.
+  #[must_use]
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  /// Returns the token at `idx`, or `None` if there is no such token.
+  #[must_use]
+  pub fn get(&self, idx: usize) -> Option<Token<'_, K>> {
+    let range = self.range(idx)?;
+    let (kind, _) = self.tokens[idx];
+    Some(Token { kind, text: &self.text[range] })
+  }
+
+  /// Returns the range in the [text](Self::text) of the token at `idx`, or `None` if there is no
+  /// such token.
+  #[must_use]
+  pub fn range(&self, idx: usize) -> Option<Range<usize>> {
+    let &(_, end) = self.tokens.get(idx)?;
+    let start = idx.checked_sub(1).map_or(0, |idx| self.tokens[idx].1);
+    Some(start..end)
+  }
+
+  /// Returns an iterator over the tokens.
+  pub fn iter(&self) -> impl Iterator<Item = Token<'_, K>> {
+    (0..self.len()).filter_map(|idx| self.get(idx))
+  }
+
+  /// Returns the tokens as borrowed tokens, as for a parser.
+  #[must_use]
+  pub fn to_tokens(&self) -> Vec<Token<'_, K>> {
+    self.iter().collect()
+  }
+}
+
+impl<'a, K> Extend<Token<'a, K>> for TokenStream<K>
+where
+  K: Copy,
+{
+  fn extend<I>(&mut self, iter: I)
+  where
+    I: IntoIterator<Item = Token<'a, K>>,
+  {
+    for token in iter {
+      self.push(token);
+    }
+  }
+}
+
+impl<'a, K> FromIterator<Token<'a, K>> for TokenStream<K>
+where
+  K: Copy,
+{
+  fn from_iter<I>(iter: I) -> Self
+  where
+    I: IntoIterator<Item = Token<'a, K>>,
+  {
+    let mut ret = Self::default();
+    ret.extend(iter);
+    ret
+  }
+}
@@ -0,0 +1,154 @@
+use crate::Token;
+use crate::lex::{self, BlockComment, Config, Error, ErrorKind, Lexed, Quoted};
+use crate::stream::TokenStream;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum K {
+  Ws,
+  LineComment,
+  BlockComment,
+  Str,
+  Num,
+  Name,
+  LetKw,
+  Eq,
+  EqEq,
+  Lt,
+  LtEq,
+  Char,
+  Invalid,
+}
+
+const PUNCTUATION: [(&[u8], K); 4] =
+  [(b"==", K::EqEq), (b"<=", K::LtEq), (b"=", K::Eq), (b"<", K::Lt)];
+
+fn keyword(bs: &[u8]) -> Option<K> {
+  match bs {
+    b"let" => Some(K::LetKw),
+    _ => None,
+  }
+}
+
+/// Char literals like `'a'`.
+fn char_lit(s: &str) -> Option<Lexed<K>> {
+  let rest = s.strip_prefix('\'')?;
+  let c = rest.chars().next()?;
+  let ok = rest[c.len_utf8()..].starts_with('\'');
+  let len = 1 + c.len_utf8() + usize::from(ok);
+  Some(Lexed { kind: K::Char, len, error: (!ok).then_some("unclosed char literal") })
+}
+
+const CONFIG: Config<'static, K> = Config {
+  hooks: &[char_lit],
+  whitespace: K::Ws,
+  line_comment: Some(("//", K::LineComment)),
+  block_comment: Some(BlockComment {
+    open: "/*",
+    close: "*/",
+    nested: true,
+    kind: K::BlockComment,
+  }),
+  string: Some(Quoted { quote: '"', escape: Some('\\'), kind: K::Str }),
+  number: Some(K::Num),
+  ident_start: |c| c.is_alphabetic() || c == '_',
+  ident_continue: |c| c.is_alphanumeric() || c == '_',
+  keyword,
+  ident: K::Name,
+  punctuation: &PUNCTUATION,
+  error: K::Invalid,
+};
+
+fn check(s: &str, want: &[(K, &str)], want_errors: &[Error]) {
+  let got = lex::get(s, &CONFIG);
+  let tokens: Vec<_> = got.tokens.iter().map(|tok| (tok.kind, tok.text)).collect();
+  assert_eq!(tokens, want);
+  assert_eq!(got.errors, want_errors);
+}
+
+#[test]
+fn smoke() {
+  check(
+    "let xs_1 <= 3.5e2 == \"a\\\"b\" // hi\n",
+    &[
+      (K::LetKw, "let"),
+      (K::Ws, " "),
+      (K::Name, "xs_1"),
+      (K::Ws, " "),
+      (K::LtEq, "<="),
+      (K::Ws, " "),
+      (K::Num, "3.5e2"),
+      (K::Ws, " "),
+      (K::EqEq, "=="),
+      (K::Ws, " "),
+      (K::Str, "\"a\\\"b\""),
+      (K::Ws, " "),
+      (K::LineComment, "// hi"),
+      (K::Ws, "\n"),
+    ],
+    &[],
+  );
+}
+
+#[test]
+fn longest_punctuation() {
+  check("<==", &[(K::LtEq, "<="), (K::Eq, "=")], &[]);
+}
+
+#[test]
+fn nested_block_comment() {
+  check("/* a /* b */ c */x", &[(K::BlockComment, "/* a /* b */ c */"), (K::Name, "x")], &[]);
+}
+
+#[test]
+fn hook() {
+  check(
+    "'é' 'a",
+    &[(K::Char, "'é'"), (K::Ws, " "), (K::Char, "'a")],
+    &[Error { range: 5..7, kind: ErrorKind::Hook("unclosed char literal") }],
+  );
+}
+
+#[test]
+fn errors() {
+  check(
+    "a # \"b",
+    &[(K::Name, "a"), (K::Ws, " "), (K::Invalid, "#"), (K::Ws, " "), (K::Str, "\"b")],
+    &[
+      Error { range: 2..3, kind: ErrorKind::InvalidSource },
+      Error { range: 4..6, kind: ErrorKind::UnclosedString },
+    ],
+  );
+  check(
+    "/* /* */",
+    &[(K::BlockComment, "/* /* */")],
+    &[Error { range: 0..8, kind: ErrorKind::UnclosedBlockComment }],
+  );
+}
+
+#[test]
+fn stream() {
+  let tokens = lex::get("let x = \"y\"", &CONFIG).tokens;
+  let stream: TokenStream<_> = tokens.iter().copied().collect();
+  assert_eq!(stream.len(), tokens.len());
+  assert_eq!(stream.text(), "let x = \"y\"");
+  assert_eq!(stream.get(2), Some(Token { kind: K::Name, text: "x" }));
+  assert_eq!(stream.range(6), Some(8..11));
+  assert_eq!(stream.get(7), None);
+  assert_eq!(stream.to_tokens(), tokens);
+}
+
+#[cfg(feature = "owned")]
+#[test]
+fn owned() {
+  use crate::owned::OwnedToken;
+  let owned: Vec<OwnedToken<K>> = {
+    let s = alloc::string::String::from("a <= b");
+    lex::get(&s, &CONFIG).tokens.into_iter().map(OwnedToken::from).collect()
+  };
+  assert_eq!(owned[2].as_token(), Token { kind: K::LtEq, text: "<=" });
+  let mut stream = TokenStream::default();
+  stream.extend(&owned);
+  assert_eq!(stream.text(), "a <= b");
+  assert_eq!(stream.get_owned(4).as_ref(), owned.get(4));
+}